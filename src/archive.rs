@@ -0,0 +1,25 @@
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::quote;
+
+
+pub fn is_archived(config: &Config, workspace: &Value) -> bool {
+    workspace["name"].as_str().is_some_and(|n| n.starts_with(&config.archive.prefix))
+}
+
+/// Rename a workspace so that it is left out of navigation
+pub fn archive_command(config: &Config, workspace: &Value) -> String {
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+    format!("rename workspace {} to {}", quote(name), quote(&format!("{}{name}", config.archive.prefix)))
+}
+
+/// Rename archived workspaces back, only the one originally called `name` if given
+pub fn unarchive_commands(config: &Config, workspaces: &[Value], name: Option<&str>) -> Vec<String> {
+    workspaces.iter()
+        .filter_map(|w| w["name"].as_str())
+        .filter_map(|archived| archived.strip_prefix(&config.archive.prefix).map(|original| (archived, original)))
+        .filter(|(_, original)| name.is_none_or(|n| n == *original))
+        .map(|(archived, original)| format!("rename workspace {} to {}", quote(archived), quote(original)))
+        .collect()
+}
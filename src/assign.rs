@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use ksway::{Client, ipc_command};
+use serde_json::Value;
+
+use crate::{Error, ipc, reply};
+use crate::output::Output;
+
+
+/// A `workspace <name> output <outputs...>` directive of the running sway config
+pub struct Assignment {
+    pub workspace: String,
+    /// Outputs in order of preference
+    pub outputs: Vec<String>,
+}
+
+impl Assignment {
+    /// Workspace number sway derives from the leading digits of the name
+    pub fn num(&self) -> Option<i64> {
+        let digits: String = self.workspace.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    }
+
+    /// Output sway puts the workspace on, the first listed one that is active when the outputs are known
+    pub fn output<'a>(&'a self, active: &[Output]) -> Option<&'a str> {
+        self.outputs.iter()
+            .find(|o| active.is_empty() || active.iter().any(|a| a.name == **o))
+            .map(String::as_str)
+    }
+}
+
+/// Assignments in the main sway config, files pulled in with `include` are not seen through IPC
+pub fn get_assignments(client: &mut Client) -> Result<Vec<Assignment>, Error> {
+    let config: Value = reply(&ipc(client, ipc_command::get_config())?)?;
+    Ok(parse(config["config"].as_str().unwrap_or_default()))
+}
+
+/// Pick the assignments out of a sway config, expanding variables defined with `set`
+pub fn parse(config: &str) -> Vec<Assignment> {
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut assignments: Vec<Assignment> = Vec::new();
+
+    for line in config.lines() {
+        let Ok(words) = shell_words::split(line.trim()) else {
+            continue;
+        };
+        let words: Vec<String> = words.into_iter()
+            .map(|w| variables.get(&w).cloned().unwrap_or(w))
+            .collect();
+
+        match words.as_slice() {
+            [set, name, value @ ..] if set == "set" && name.starts_with('$') => {
+                variables.insert(name.to_owned(), value.join(" "));
+            },
+            [workspace, rest @ ..] if workspace == "workspace" => {
+                let Some(at) = rest.iter().position(|w| w == "output") else {
+                    continue;
+                };
+                let name: &[String] = match &rest[..at] {
+                    [number, name @ ..] if number == "number" => name,
+                    name => name,
+                };
+                if !name.is_empty() && at + 1 < rest.len() {
+                    assignments.push(Assignment { workspace: name.join(" "), outputs: rest[at + 1..].to_vec() });
+                }
+            },
+            _ => {},
+        }
+    }
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn parse_expands_set_variables() {
+        let config: &str = "\
+set $left DP-1
+set $right HDMI-A-1
+set $web \"2:web\"
+workspace 1 output $left
+workspace $web output $right $left
+workspace number 3 output eDP-1
+bindsym $mod+1 workspace number 1
+";
+        let assignments: Vec<(String, Vec<String>)> = parse(config).into_iter().map(|a| (a.workspace, a.outputs)).collect();
+        assert_eq!(assignments, [
+            ("1".to_string(), vec!["DP-1".to_string()]),
+            ("2:web".to_string(), vec!["HDMI-A-1".to_string(), "DP-1".to_string()]),
+            ("3".to_string(), vec!["eDP-1".to_string()]),
+        ]);
+    }
+}
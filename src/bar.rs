@@ -0,0 +1,209 @@
+use std::io::{BufRead, Write, stdin, stdout};
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+
+use ksway::Client;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::daemon::{self, Event, Kind};
+use crate::state::State;
+use crate::{display, get_workspaces, order_key, run_words, state};
+
+
+/// Action lines read from stdin, like `next` for a scroll up
+fn read_actions(tx: Sender<Event>) {
+    thread::spawn(move || {
+        for line in stdin().lock().lines().map_while(Result::ok) {
+            if tx.send(Event::Command(line.trim().to_string())).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// How a bar talks: what comes before the first line, how the workspaces make a line, and what a line
+/// read from stdin asks for
+struct Protocol {
+    header: Option<&'static str>,
+    /// Gets the workspaces in navigation order
+    render: fn(&Config, &State, &[Value]) -> String,
+    action: fn(&str) -> Option<Vec<String>>,
+}
+
+/// Action lines as they are, like `next` for a scroll up
+fn action_line(line: &str) -> Option<Vec<String>> {
+    shell_words::split(line).ok().filter(|words| !words.is_empty())
+}
+
+/// Print a line for the workspaces, false once nobody reads stdout or sway does not answer
+fn print(client: &mut Client, config: &Config, protocol: &Protocol) -> bool {
+    let Ok(mut workspaces) = get_workspaces(client) else {
+        return false;
+    };
+    workspaces.sort_by_key(order_key);
+    writeln!(stdout(), "{}", (protocol.render)(config, &state::load(), &workspaces)).is_ok()
+}
+
+/// Print a line for the workspaces at start and on every workspace event, and run what the lines read
+/// from stdin ask for, until the connection to sway closes or nobody reads stdout
+fn serve(client: &mut Client, config: &Config, protocol: Protocol) {
+    let (tx, rx) = channel();
+    read_actions(tx.clone());
+    daemon::subscribe(client, tx);
+    if let Some(header) = protocol.header {
+        println!("{header}");
+    }
+    if !print(client, config, &protocol) {
+        return;
+    }
+
+    for event in rx {
+        match event {
+            Event::Command(line) => {
+                let Some(words) = (protocol.action)(&line) else {
+                    continue;
+                };
+                if let Err(err) = run_words(client, config, &words) {
+                    eprintln!("{}: {err}", words.join(" "));
+                }
+            },
+            Event::Sway(Kind::Workspace, _) if !print(client, config, &protocol) => break,
+            Event::Disconnected => break,
+            _ => {},
+        }
+    }
+}
+
+/// Waybar custom module JSON: the focused workspace as text, every workspace by output in the tooltip
+/// and classes for urgency and the tags of the focused workspace
+fn render_waybar(config: &Config, state: &State, workspaces: &[Value]) -> String {
+    let name = |w: &Value| display::name(config, w["name"].as_str().unwrap_or_default());
+    let focused: Option<&Value> = workspaces.iter().find(|w| w["focused"] == true);
+
+    let mut outputs: Vec<&str> = workspaces.iter().filter_map(|w| w["output"].as_str()).collect();
+    outputs.sort();
+    outputs.dedup();
+    let tooltip: Vec<String> = outputs.iter()
+        .map(|output| {
+            let names: Vec<String> = workspaces.iter()
+                .filter(|w| w["output"] == *output)
+                .map(|w| if w["focused"] == true { format!("[{}]", name(w)) } else { name(w) })
+                .collect();
+            format!("{output}: {}", names.join(" "))
+        })
+        .collect();
+
+    let mut class: Vec<String> = Vec::new();
+    if workspaces.iter().any(|w| w["urgent"] == true) {
+        class.push("urgent".to_string());
+    }
+    if let Some(label) = focused.and_then(|w| state.labels.get(w["name"].as_str()?)) {
+        class.extend(label.tags.iter().cloned());
+    }
+
+    json!({
+        "text": focused.map(name).unwrap_or_default(),
+        "tooltip": tooltip.join("\n"),
+        "class": class,
+    }).to_string()
+}
+
+/// Run as a Waybar custom module with `"return-type": "json"`
+pub fn waybar(client: &mut Client, config: &Config) {
+    serve(client, config, Protocol { header: None, render: render_waybar, action: action_line });
+}
+
+/// The focused workspace with the one before and after it on its output
+fn neighbours(workspaces: &[Value]) -> Vec<&Value> {
+    let Some(focused) = workspaces.iter().find(|w| w["focused"] == true) else {
+        return Vec::new();
+    };
+    let on_output: Vec<&Value> = workspaces.iter().filter(|w| w["output"] == focused["output"]).collect();
+    let at: usize = on_output.iter().position(|w| w["id"] == focused["id"]).unwrap_or_default();
+    on_output[at.saturating_sub(1)..(at + 2).min(on_output.len())].to_vec()
+}
+
+fn label(config: &Config, workspace: &Value) -> String {
+    let name: String = display::name(config, workspace["name"].as_str().unwrap_or_default());
+    if workspace["focused"] == true { format!("[{name}]") } else { name }
+}
+
+/// One i3bar status line, a block per neighbouring workspace
+fn render_i3bar(config: &Config, state: &State, workspaces: &[Value]) -> String {
+    let blocks: Vec<Value> = neighbours(workspaces).into_iter()
+        .map(|w| {
+            let name: &str = w["name"].as_str().unwrap_or_default();
+            json!({
+                "name": "workspace",
+                "instance": name,
+                "full_text": label(config, w),
+                "short_text": w["num"].as_i64().filter(|n| *n >= 0).map_or_else(|| label(config, w), |n| display::number(config, n)),
+                "urgent": w["urgent"] == true,
+                "color": state.labels.get(name).and_then(|l| l.color.to_owned()),
+            })
+        })
+        .collect();
+    format!("{},", Value::from(blocks))
+}
+
+/// i3blocks persistent blocks take each line as the new text
+fn render_i3blocks(config: &Config, _state: &State, workspaces: &[Value]) -> String {
+    neighbours(workspaces).into_iter().map(|w| label(config, w)).collect::<Vec<String>>().join(" ")
+}
+
+/// Click events: the left button switches to the workspace clicked, the wheel steps on its output
+fn click(line: &str) -> Option<Vec<String>> {
+    let event: Value = serde_json::from_str(line.trim_start_matches([',', '['])).ok()?;
+    let words: Vec<&str> = match event["button"].as_u64()? {
+        1 if event["name"] == "workspace" => vec!["goto-name", event["instance"].as_str()?],
+        1 => return None,
+        4 => vec!["prev-on-output"],
+        5 => vec!["next-on-output"],
+        _ => return None,
+    };
+    Some(words.into_iter().map(String::from).collect())
+}
+
+/// Text inside a polybar action tag, where a colon ends the command
+fn action_tag(button: u8, command: &str, text: &str) -> String {
+    format!("%{{A{button}:{}:}}{text}%{{A}}", command.replace(':', "\\:"))
+}
+
+/// One polybar line: every workspace clickable, the focused one underlined, in their label color,
+/// and the wheel stepping along the focused output
+fn render_polybar(config: &Config, state: &State, workspaces: &[Value]) -> String {
+    let labels: Vec<String> = workspaces.iter()
+        .map(|w| {
+            let name: &str = w["name"].as_str().unwrap_or_default();
+            let mut text: String = display::name(config, name);
+            if let Some(color) = state.labels.get(name).and_then(|l| l.color.as_deref()) {
+                text = format!("%{{F{color}}}{text}%{{F-}}");
+            }
+            if w["focused"] == true {
+                text = format!("%{{+u}}{text}%{{-u}}");
+            }
+            if w["urgent"] == true {
+                text = format!("%{{R}}{text}%{{R}}");
+            }
+            action_tag(1, &format!("sway-workspace goto-name {}", shell_words::quote(name)), &text)
+        })
+        .collect();
+    let line: String = action_tag(4, "sway-workspace prev-on-output", &labels.join(" "));
+    action_tag(5, "sway-workspace next-on-output", &line)
+}
+
+/// Run as a polybar `custom/script` module with `tail = true`
+pub fn polybar(client: &mut Client, config: &Config) {
+    serve(client, config, Protocol { header: None, render: render_polybar, action: action_line });
+}
+
+/// Run as a status command of i3bar, or with `blocks` as a persistent i3blocks block
+pub fn i3bar(client: &mut Client, config: &Config, blocks: bool) {
+    let protocol = if blocks {
+        Protocol { header: None, render: render_i3blocks, action: click }
+    } else {
+        Protocol { header: Some("{\"version\":1,\"click_events\":true}\n["), render: render_i3bar, action: click }
+    };
+    serve(client, config, protocol);
+}
@@ -0,0 +1,909 @@
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+
+use crate::config::Order;
+use crate::output::Direction;
+use crate::{Action, Args, Format, schema, tree};
+
+
+/// Simple command to switch workspaces with optional output awareness for Sway/i3
+#[derive(Parser)]
+#[command(author, version, about, long_about = None, disable_help_subcommand = true)]
+pub struct Cli {
+   /// Sway/i3 socket path, `sock` in the config or $SWAYSOCK by default
+   #[arg(short, long, global = true)]
+   sock: Option<String>,
+
+   /// JSON schema version to print, fails when this build does not speak it
+   #[arg(long, global = true, default_value_t = schema::VERSION)]
+   schema_version: u64,
+
+   /// Hand the action to the running daemon, which is connected to sway already; without a daemon it runs here
+   #[arg(long, global = true, default_value_t = false)]
+   via_daemon: bool,
+
+   #[command(subcommand)]
+   command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Next workspace by number, a new one after the last
+    Next {
+        /// Switch every output together, stepping the workspace index of all outputs
+        #[arg(short, long, default_value_t = false)]
+        global: bool,
+        /// Go through the workspaces output by output, on to the next output after the last one of each
+        #[arg(long, default_value_t = false, conflicts_with = "global")]
+        layout: bool,
+        #[command(flatten)]
+        step: Step,
+    },
+    /// Previous workspace by number
+    Prev {
+        /// Switch every output together, stepping the workspace index of all outputs
+        #[arg(short, long, default_value_t = false)]
+        global: bool,
+        /// Go through the workspaces output by output, back to the previous output before the first one of each
+        #[arg(long, default_value_t = false, conflicts_with = "global")]
+        layout: bool,
+        #[command(flatten)]
+        step: Step,
+    },
+    /// Visible workspace of the next output
+    NextOutput(Cycle),
+    /// Visible workspace of the previous output
+    PrevOutput(Cycle),
+    /// Next workspace of the focused output
+    NextOnOutput(Step),
+    /// Previous workspace of the focused output
+    PrevOnOutput(Step),
+    /// Workspace N on the first output and N plus their offset on the others
+    SyncGoto {
+        #[arg(allow_negative_numbers = true)]
+        n: i64,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Workspace N of the focused output's decade, its offset plus N
+    GotoLocal {
+        n: i64,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Send a digit to the daemon, digits typed in a row make one workspace number
+    Digit {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=9))]
+        digit: u8,
+    },
+    /// Next workspace in most recently used order, steps in a row form one cycle in the daemon
+    MruNext,
+    /// Previous workspace of the daemon's most recently used cycle
+    MruPrev,
+    /// End the daemon's most recently used cycle at the workspace it shows
+    MruCommit,
+    /// Run the daemon
+    Daemon,
+    /// Move every container of the focused workspace to another one
+    MoveAll {
+        /// Target workspace number
+        #[arg(long)]
+        to: i64,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Exchange the numbers of two workspaces, keeping the rest of their names
+    Swap {
+        #[arg(value_name = "A")]
+        workspace: String,
+        /// Workspace or number to swap with, the next workspace after A by default
+        #[arg(value_name = "B")]
+        other: Option<String>,
+    },
+    /// Swap numbers with the workspace before the focused one, moving it one place to the left
+    ShiftLeft,
+    /// Swap numbers with the workspace after the focused one, moving it one place to the right
+    ShiftRight,
+    /// Remember the focused workspace under a key, or go back to it
+    Bookmark {
+        #[arg(value_parser = ["set", "go"])]
+        op: String,
+        key: String,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Remember the focused workspace for pop, then run ACTION if given
+    Push {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "ACTION")]
+        action: Vec<String>,
+    },
+    /// Go back to the workspace pushed last
+    Pop(Switch),
+    /// Workspace focused before the current one, wherever it is
+    BackAndForth(Switch),
+    /// Previous workspace in the history of switches
+    Back(Switch),
+    /// Next workspace in the history of switches, after going back
+    Forward(Switch),
+    /// Workspace by number or name, which has to exist unless --create is given
+    Goto {
+        #[arg(value_name = "WS")]
+        workspace: String,
+        /// Create the workspace when it does not exist
+        #[arg(long, default_value_t = false)]
+        create: bool,
+        /// Switch every output together to workspace index WS, like sync-goto
+        #[arg(short, long, default_value_t = false)]
+        global: bool,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Lowest workspace number not in use, a fresh empty workspace
+    New(Switch),
+    /// New workspace right after the focused one, numbering every later workspace up by one
+    Insert(Switch),
+    /// Move the focused window to the lowest workspace number not in use, and follow it
+    Isolate(Switch),
+    /// Next free number in the focused output's range, or after its workspaces, opened on that output
+    NewOnOutput(Switch),
+    /// The number after the focused workspace if it is free, or else the next free one after it
+    NextFree(Switch),
+    /// Nearest workspace with an urgent window, fails when there is none
+    Urgent(Switch),
+    /// Workspace of the first window matching all criteria given
+    GotoWindow {
+        #[command(flatten)]
+        criteria: Criteria,
+        /// Focus that window too
+        #[arg(long, default_value_t = false)]
+        focus_window: bool,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Workspace by its full name
+    GotoName {
+        name: String,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Print a completion script
+    Completions {
+        #[arg(value_parser = ["bash", "zsh", "fish"])]
+        shell: String,
+    },
+    /// Show the workspaces of a set on their outputs
+    Set {
+        #[arg(value_parser = ["activate"])]
+        op: String,
+        name: String,
+        /// Create missing workspaces of the set
+        #[arg(long, default_value_t = false)]
+        create: bool,
+        #[command(flatten)]
+        switch: Switch,
+    },
+    /// Print the project directory of the focused workspace
+    Cwd {
+        #[command(flatten)]
+        output: Output,
+    },
+    /// Start the apps of a template from the config together on a fresh workspace
+    Open {
+        name: String,
+    },
+    /// Save the container tree of a workspace under NAME, or rebuild it from the windows there are
+    Layout {
+        #[arg(value_parser = ["save", "restore"])]
+        op: String,
+        name: String,
+        #[command(flatten)]
+        workspace: Chosen,
+    },
+    /// Choose a workspace with the configured picker
+    Pick(Switch),
+    /// Rename archived workspaces back, all of them without NAME
+    Unarchive {
+        name: Option<String>,
+        #[command(flatten)]
+        bulk: Bulk,
+    },
+    /// Set or clear the color and tags of a workspace
+    Label {
+        #[arg(value_parser = ["set", "clear"])]
+        op: String,
+        #[arg(value_name = "WS")]
+        workspace: String,
+        /// Color for label set
+        #[arg(long)]
+        color: Option<String>,
+        /// Tag for label set, can be given more than once
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Switch to a group of workspaces or move the workspace into one
+    Group {
+        #[arg(value_parser = ["switch", "move", "clear"])]
+        op: String,
+        /// Group for group switch and group move, group clear shows every group again
+        #[arg(required_if_eq_any([("op", "switch"), ("op", "move")]))]
+        name: Option<String>,
+    },
+    /// Rename a workspace to its number and LABEL, replacing the label it had
+    Rename {
+        #[arg(required_unless_present = "clear")]
+        label: Option<String>,
+        /// Drop the label, leaving only the number
+        #[arg(long, default_value_t = false, conflicts_with = "label")]
+        clear: bool,
+        #[command(flatten)]
+        workspace: Chosen,
+    },
+    /// Print the JSON Schema of --format json
+    Schema,
+    /// Spread the workspaces over the outputs again
+    Rebalance(Bulk),
+    /// Even out the workspaces of the outputs with few moves, keeping pinned ones and ranges
+    Balance(Bulk),
+    /// Exchange the workspaces two outputs show, the focused and the next one by default
+    SwapOutputs {
+        #[arg(requires = "b")]
+        a: Option<String>,
+        b: Option<String>,
+    },
+    /// Hand the workspace every output shows to the next output from left to right
+    RotateOutputs {
+        /// Hand them to the output before instead
+        #[arg(long, default_value_t = false)]
+        reverse: bool,
+    },
+    /// Move the focused workspace to the output next to its own in a direction, to an output by name,
+    /// or to the Nth output from left to right
+    MoveToOutput {
+        #[arg(value_name = "left|right|up|down|OUTPUT|N")]
+        to: String,
+        /// Go round to the output furthest the other way when there is none in the direction
+        #[arg(short, long, default_value_t = false)]
+        wrap: bool,
+        /// Keep the focus on the output the workspace leaves
+        #[arg(short, long = "no-focus", default_value_t = false)]
+        no_focus: bool,
+        /// Move the pointer along to the centre of the output the focus goes to
+        #[arg(long, default_value_t = false, conflicts_with = "no_focus")]
+        warp_cursor: bool,
+    },
+    /// Focus another output, leaving what the outputs show as it is
+    FocusOutput {
+        #[arg(value_parser = ["next", "prev", "left", "right", "up", "down"])]
+        to: String,
+        /// Go round to the output furthest the other way when there is none in the direction
+        #[arg(short, long, default_value_t = false)]
+        wrap: bool,
+        /// Move the pointer along to the centre of the output the focus goes to
+        #[arg(long, default_value_t = false)]
+        warp_cursor: bool,
+    },
+    /// Move every workspace to the focused output
+    Gather {
+        /// Only the workspaces no output shows
+        #[arg(long, default_value_t = false)]
+        hidden: bool,
+        #[command(flatten)]
+        bulk: Bulk,
+    },
+    /// Number the workspaces 1 to n in their order again, closing the gaps
+    Compact {
+        /// Give every output a block of numbers of its own, from the start of its range if it has one
+        #[arg(long, default_value_t = false)]
+        per_output: bool,
+        #[command(flatten)]
+        bulk: Bulk,
+    },
+    /// Print what the daemon keeps track of
+    Query {
+        #[arg(value_parser = ["prev-workspace"])]
+        what: String,
+        /// Output to query, the focused one by default
+        #[arg(long, value_name = "OUTPUT")]
+        output: Option<String>,
+        #[command(flatten)]
+        format: Output,
+    },
+    /// Run actions for buttons read from stdin
+    Deck,
+    /// Print a line of JSON for every workspace event until killed
+    Follow,
+    /// Run as a Waybar custom module, running action lines read from stdin
+    Waybar,
+    /// Speak the i3bar protocol with the focused workspace and its neighbours, clicks switch
+    I3bar {
+        /// Print plain lines for a persistent i3blocks block instead
+        #[arg(long, default_value_t = false)]
+        blocks: bool,
+    },
+    /// Print polybar lines with click actions for a tailed custom/script module
+    Polybar,
+    /// Capture the output showing a workspace
+    Shot {
+        #[command(flatten)]
+        workspace: Chosen,
+        /// File to write
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+        /// Switch to a hidden workspace for the capture
+        #[arg(long, default_value_t = false)]
+        reveal: bool,
+    },
+    /// List the windows of a workspace
+    Windows {
+        #[command(flatten)]
+        workspace: Chosen,
+        #[command(flatten)]
+        format: Output,
+    },
+    /// Close every window of a workspace
+    KillAll {
+        #[command(flatten)]
+        workspace: Chosen,
+        /// Criteria of windows to leave open, like '[app_id="foot"]'
+        #[arg(long, value_name = "CRITERIA")]
+        except: Option<String>,
+        #[command(flatten)]
+        bulk: Bulk,
+    },
+    /// Activate a profile by hand, or clear it to follow the schedule again
+    Profile {
+        #[arg(value_parser = ["activate", "clear"])]
+        op: String,
+        name: Option<String>,
+        /// Create missing workspaces of the profile's set
+        #[arg(long, default_value_t = false)]
+        create: bool,
+        #[command(flatten)]
+        switch: Switch,
+    },
+}
+
+/// How an action gets to its target workspace
+#[derive(clap::Args)]
+struct Switch {
+   /// Move the focused container to new workspace
+   #[arg(short, long = "move", visible_alias = "move-window", default_value_t = false)]
+   move_ws: bool,
+
+   /// Move every window of the current workspace to the target, folding it into the target
+   #[arg(long, default_value_t = false, conflicts_with = "move_ws")]
+   merge: bool,
+
+   /// Do not focus to new workspace
+   #[arg(short, long = "no-focus", default_value_t = false)]
+   no_focus_ws: bool,
+
+   /// Focus the moved window at the new workspace
+   #[arg(short = 'f', long = "focus-moved", visible_alias = "keep-focus", default_value_t = false)]
+   focus_moved: bool,
+
+   /// Start from the workspace on the output under the mouse pointer instead of the focused one
+   #[arg(long, default_value_t = false)]
+   under_cursor: bool,
+
+   /// Start from the workspace OUTPUT shows instead of the focused one
+   #[arg(long, value_name = "OUTPUT", conflicts_with = "under_cursor")]
+   on_output: Option<String>,
+
+   /// Output to put the target workspace on when it does not exist yet
+   #[arg(long, value_name = "OUTPUT")]
+   create_on: Option<String>,
+
+   /// Focus the window that was focused last on the target workspace
+   #[arg(long, default_value_t = false)]
+   focus_last: bool,
+
+   /// Move the pointer to the centre of the output the target workspace is on, when it is another output
+   #[arg(long, default_value_t = false)]
+   warp_cursor: bool,
+
+   /// Switch even when a guard in the config would refuse it
+   #[arg(long, default_value_t = false)]
+   force: bool,
+
+   /// Run bulk changes, like --merge or what insert renumbers, without asking first
+   #[arg(short, long, default_value_t = false)]
+   yes: bool,
+
+   /// Print workspace number to stdout
+   #[arg(short = 'o', long = "stdout", default_value_t = false)]
+   stdout_ws: bool,
+
+   #[command(flatten)]
+   output: Output,
+}
+
+/// Steps through the outputs
+#[derive(clap::Args)]
+struct Cycle {
+   /// Go round from the last output to the first and back, like `[navigation] wrap_outputs`
+   #[arg(short, long, default_value_t = false)]
+   wrap: bool,
+
+   /// Stop at the first and last output even when `[navigation] wrap_outputs` is set
+   #[arg(long, default_value_t = false, overrides_with = "wrap")]
+   no_wrap: bool,
+
+   #[command(flatten)]
+   switch: Switch,
+}
+
+/// Relative steps through the workspaces
+#[derive(clap::Args)]
+struct Step {
+   /// Go round from the last workspace to the first and back instead of creating or stopping
+   #[arg(short, long, default_value_t = false)]
+   wrap: bool,
+
+   /// Create or stop past the ends even when `[navigation] wrap` is set
+   #[arg(long, default_value_t = false, overrides_with = "wrap")]
+   no_wrap: bool,
+
+   /// Order to step through the workspaces in, `[navigation] order` in the config by default
+   #[arg(long, value_enum)]
+   order: Option<Order>,
+
+   /// Pass over these workspaces, numbers, names or `/regex/`, on top of `[navigation] skip`
+   #[arg(long, value_name = "WS,...", value_delimiter = ',')]
+   skip: Vec<String>,
+
+   /// Pass over workspaces without windows
+   #[arg(long, default_value_t = false)]
+   skip_empty: bool,
+
+   /// Take this many steps at once, short `-c` since `-n` is --no-focus
+   #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+   count: u32,
+
+   #[command(flatten)]
+   switch: Switch,
+}
+
+#[derive(clap::Args)]
+struct Output {
+   /// Output format
+   #[arg(long, value_enum, default_value_t = Format::Text)]
+   format: Format,
+}
+
+/// The workspace an action is about
+#[derive(clap::Args)]
+struct Chosen {
+   /// Workspace by name or number, the focused one by default
+   #[arg(long, value_name = "WS")]
+   workspace: Option<String>,
+}
+
+/// Which windows to look for
+#[derive(clap::Args)]
+#[command(group(ArgGroup::new("criteria").required(true).multiple(true).args(["app_id", "class", "title"])))]
+struct Criteria {
+   /// Wayland app id
+   #[arg(long, value_name = "APP_ID")]
+   app_id: Option<String>,
+
+   /// X11 window class
+   #[arg(long)]
+   class: Option<String>,
+
+   /// Regular expression the title has to match
+   #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+   title: Option<Regex>,
+}
+
+/// Changes that ask before they run
+#[derive(clap::Args)]
+struct Bulk {
+   /// Run without asking first
+   #[arg(short, long, default_value_t = false)]
+   yes: bool,
+}
+
+impl Switch {
+    fn apply(self, args: &mut Args) {
+        args.move_ws = self.move_ws;
+        args.yes = self.yes;
+        args.merge = self.merge;
+        args.no_focus_ws = self.no_focus_ws;
+        args.focus_moved = self.focus_moved;
+        args.under_cursor = self.under_cursor;
+        args.on_output = self.on_output;
+        args.create_on = self.create_on;
+        args.focus_last = self.focus_last;
+        args.warp_cursor = self.warp_cursor;
+        args.force = self.force;
+        args.stdout_ws = self.stdout_ws;
+        args.format = self.output.format;
+    }
+}
+
+impl Step {
+    fn apply(self, args: &mut Args) {
+        args.wrap = match (self.wrap, self.no_wrap) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        args.order = self.order;
+        args.skip = self.skip;
+        args.skip_empty = self.skip_empty;
+        args.count = self.count;
+        self.switch.apply(args);
+    }
+}
+
+impl Cycle {
+    fn apply(self, args: &mut Args) {
+        args.wrap = match (self.wrap, self.no_wrap) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        self.switch.apply(args);
+    }
+}
+
+impl Cli {
+    fn into_args(self) -> Result<Args, clap::Error> {
+        let mut args = Args::new(self.sock, self.schema_version);
+        args.via_daemon = self.via_daemon;
+        match self.command {
+            Command::Next { global, layout, step } => {
+                args.action = Action::Next;
+                args.global = global;
+                args.layout_aware = layout;
+                step.apply(&mut args);
+            },
+            Command::Prev { global, layout, step } => {
+                args.action = Action::Prev;
+                args.global = global;
+                args.layout_aware = layout;
+                step.apply(&mut args);
+            },
+            Command::NextOutput(cycle) => {
+                args.action = Action::NextOutput;
+                cycle.apply(&mut args);
+            },
+            Command::PrevOutput(cycle) => {
+                args.action = Action::PrevOutput;
+                cycle.apply(&mut args);
+            },
+            Command::NextOnOutput(step) => {
+                args.action = Action::NextOnOutput;
+                step.apply(&mut args);
+            },
+            Command::PrevOnOutput(step) => {
+                args.action = Action::PrevOnOutput;
+                step.apply(&mut args);
+            },
+            Command::SyncGoto { n, switch } => {
+                args.action = Action::SyncGoto;
+                args.params = vec![n.to_string()];
+                switch.apply(&mut args);
+            },
+            Command::GotoLocal { n, switch } => {
+                args.action = Action::GotoLocal;
+                args.params = vec![n.to_string()];
+                switch.apply(&mut args);
+            },
+            Command::Digit { digit } => {
+                args.action = Action::Digit;
+                args.params = vec![digit.to_string()];
+            },
+            Command::MruNext => {
+                args.action = Action::Mru;
+                args.params = vec!["next".to_string()];
+            },
+            Command::MruPrev => {
+                args.action = Action::Mru;
+                args.params = vec!["prev".to_string()];
+            },
+            Command::MruCommit => {
+                args.action = Action::Mru;
+                args.params = vec!["commit".to_string()];
+            },
+            Command::Daemon => args.action = Action::Daemon,
+            Command::MoveAll { to, switch } => {
+                args.action = Action::MoveAll;
+                args.to = Some(to);
+                switch.apply(&mut args);
+            },
+            Command::Swap { workspace, other } => {
+                args.action = Action::Swap;
+                args.params = std::iter::once(workspace).chain(other).collect();
+            },
+            Command::ShiftLeft => args.action = Action::ShiftLeft,
+            Command::ShiftRight => args.action = Action::ShiftRight,
+            Command::Bookmark { op, key, switch } => {
+                args.action = Action::Bookmark;
+                args.params = vec![op, key];
+                switch.apply(&mut args);
+            },
+            Command::Push { action } if action.is_empty() => args.action = Action::Push,
+            Command::Push { action } => {
+                // The pushed action is parsed on its own, with the global flags given before push
+                let words: Vec<String> = args.sock.into_iter().flat_map(|sock| ["--sock".to_string(), sock])
+                    .chain(["--schema-version".to_string(), args.schema_version.to_string()])
+                    .chain(action.iter().cloned())
+                    .collect();
+                let mut inner: Args = parse(&words)?;
+                if matches!(inner.action, Action::Push | Action::Pop | Action::Digit | Action::Mru | Action::Daemon | Action::Completions | Action::Schema | Action::Deck | Action::Follow | Action::Waybar | Action::I3bar | Action::Polybar) {
+                    return Err(Cli::command().error(ErrorKind::InvalidValue, format!("cannot push {}", action[0])));
+                }
+                inner.push = true;
+                inner.via_daemon |= args.via_daemon;
+                return Ok(inner);
+            },
+            Command::Pop(switch) => {
+                args.action = Action::Pop;
+                switch.apply(&mut args);
+            },
+            Command::BackAndForth(switch) => {
+                args.action = Action::BackAndForth;
+                switch.apply(&mut args);
+            },
+            Command::Back(switch) => {
+                args.action = Action::Back;
+                switch.apply(&mut args);
+            },
+            Command::Forward(switch) => {
+                args.action = Action::Forward;
+                switch.apply(&mut args);
+            },
+            Command::Goto { workspace, create, global, switch } => {
+                args.action = Action::Goto;
+                args.params = vec![workspace];
+                args.create = create;
+                args.global = global;
+                switch.apply(&mut args);
+            },
+            Command::New(switch) => {
+                args.action = Action::New;
+                switch.apply(&mut args);
+            },
+            Command::Insert(switch) => {
+                args.action = Action::Insert;
+                switch.apply(&mut args);
+            },
+            Command::Isolate(switch) => {
+                args.action = Action::Isolate;
+                switch.apply(&mut args);
+                // The focused container goes, and it stays focused unless --no-focus
+                args.move_ws = true;
+                args.merge = false;
+                args.focus_moved = !args.no_focus_ws;
+            },
+            Command::NewOnOutput(switch) => {
+                args.action = Action::NewOnOutput;
+                switch.apply(&mut args);
+            },
+            Command::NextFree(switch) => {
+                args.action = Action::NextFree;
+                switch.apply(&mut args);
+            },
+            Command::Urgent(switch) => {
+                args.action = Action::Urgent;
+                switch.apply(&mut args);
+            },
+            Command::GotoWindow { criteria, focus_window, switch } => {
+                args.action = Action::GotoWindow;
+                args.criteria = tree::Criteria { app_id: criteria.app_id, class: criteria.class, title: criteria.title };
+                args.focus_window = focus_window;
+                switch.apply(&mut args);
+            },
+            Command::GotoName { name, switch } => {
+                args.action = Action::GotoName;
+                args.params = vec![name];
+                switch.apply(&mut args);
+            },
+            Command::Completions { shell } => {
+                args.action = Action::Completions;
+                args.params = vec![shell];
+            },
+            Command::Set { op, name, create, switch } => {
+                args.action = Action::Set;
+                args.params = vec![op, name];
+                args.create = create;
+                switch.apply(&mut args);
+            },
+            Command::Cwd { output } => {
+                args.action = Action::Cwd;
+                args.format = output.format;
+            },
+            Command::Open { name } => {
+                args.action = Action::Open;
+                args.params = vec![name];
+            },
+            Command::Layout { op, name, workspace } => {
+                args.action = Action::Layout;
+                args.params = vec![op, name];
+                args.workspace = workspace.workspace;
+            },
+            Command::Pick(switch) => {
+                args.action = Action::Pick;
+                switch.apply(&mut args);
+            },
+            Command::Unarchive { name, bulk } => {
+                args.action = Action::Unarchive;
+                args.params = name.into_iter().collect();
+                args.yes = bulk.yes;
+            },
+            Command::Label { op, workspace, color, tags } => {
+                args.action = Action::Label;
+                args.params = vec![op, workspace];
+                args.color = color;
+                args.tags = tags;
+            },
+            Command::Group { op, name } => {
+                args.action = Action::Group;
+                args.params = [op].into_iter().chain(name).collect();
+            },
+            Command::Rename { label, clear: _, workspace } => {
+                args.action = Action::Rename;
+                args.params = label.into_iter().collect();
+                args.workspace = workspace.workspace;
+            },
+            Command::Schema => args.action = Action::Schema,
+            Command::Follow => args.action = Action::Follow,
+            Command::Waybar => args.action = Action::Waybar,
+            Command::I3bar { blocks } => {
+                args.action = Action::I3bar;
+                args.blocks = blocks;
+            },
+            Command::Polybar => args.action = Action::Polybar,
+            Command::Rebalance(bulk) => {
+                args.action = Action::Rebalance;
+                args.yes = bulk.yes;
+            },
+            Command::Balance(bulk) => {
+                args.action = Action::Balance;
+                args.yes = bulk.yes;
+            },
+            Command::SwapOutputs { a, b } => {
+                args.action = Action::SwapOutputs;
+                args.params = a.into_iter().chain(b).collect();
+            },
+            Command::RotateOutputs { reverse } => {
+                args.action = Action::RotateOutputs;
+                args.reverse = reverse;
+            },
+            Command::MoveToOutput { to, wrap, no_focus, warp_cursor } => {
+                args.action = Action::MoveToOutput;
+                args.direction = Direction::from_str(&to, true).ok();
+                args.params = vec![to];
+                args.wrap = wrap.then_some(true);
+                args.no_focus_ws = no_focus;
+                args.warp_cursor = warp_cursor;
+            },
+            Command::FocusOutput { to, wrap, warp_cursor } => {
+                args.action = Action::FocusOutput;
+                args.direction = Direction::from_str(&to, true).ok();
+                args.params = vec![to];
+                args.wrap = wrap.then_some(true);
+                args.warp_cursor = warp_cursor;
+            },
+            Command::Gather { hidden, bulk } => {
+                args.action = Action::Gather;
+                args.hidden = hidden;
+                args.yes = bulk.yes;
+            },
+            Command::Compact { per_output, bulk } => {
+                args.action = Action::Compact;
+                args.per_output = per_output;
+                args.yes = bulk.yes;
+            },
+            Command::Query { what, output, format } => {
+                args.action = Action::Query;
+                args.params = vec![what];
+                args.output = output;
+                args.format = format.format;
+            },
+            Command::Deck => args.action = Action::Deck,
+            Command::Shot { workspace, out, reveal } => {
+                args.action = Action::Shot;
+                args.workspace = workspace.workspace;
+                args.out = Some(out);
+                args.reveal = reveal;
+            },
+            Command::Windows { workspace, format } => {
+                args.action = Action::Windows;
+                args.workspace = workspace.workspace;
+                args.format = format.format;
+            },
+            Command::KillAll { workspace, except, bulk } => {
+                args.action = Action::KillAll;
+                args.workspace = workspace.workspace;
+                args.except = except;
+                args.yes = bulk.yes;
+            },
+            Command::Profile { op, name, create, switch } => {
+                args.action = Action::Profile;
+                args.params = [op].into_iter().chain(name).collect();
+                args.create = create;
+                switch.apply(&mut args);
+            },
+        }
+        Ok(args)
+    }
+}
+
+/// Put the action of the older `sway-workspace [FLAGS] <ACTION> [PARAMS]` form in front of the flags,
+/// so that sway configs written for it keep working
+fn legacy_order(words: &[String]) -> Vec<String> {
+    let command = Cli::command();
+    let takes_value = |word: &str| {
+        std::iter::once(&command).chain(command.get_subcommands())
+            .flat_map(|c| c.get_arguments())
+            .any(|a| {
+                a.get_action().takes_values()
+                    && (a.get_long().is_some_and(|l| word == format!("--{l}"))
+                        || a.get_short().is_some_and(|s| word == format!("-{s}")))
+            })
+    };
+
+    let mut skip = false;
+    for (index, word) in words.iter().enumerate() {
+        if skip {
+            skip = false;
+        } else if word.starts_with('-') {
+            skip = takes_value(word);
+        } else {
+            if index > 0 && command.find_subcommand(word).is_some() {
+                let mut reordered: Vec<String> = words.to_vec();
+                let action: String = reordered.remove(index);
+                reordered.insert(0, action);
+                return reordered;
+            }
+            break;
+        }
+    }
+    words.to_vec()
+}
+
+/// Parse command line words, without the program name, into the options of their action
+pub fn parse(words: &[String]) -> Result<Args, clap::Error> {
+    let words: Vec<String> = legacy_order(words);
+    Cli::try_parse_from(std::iter::once("sway-workspace".to_string()).chain(words))?.into_args()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{legacy_order, parse};
+    use crate::Action;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn legacy_order_moves_the_action_in_front() {
+        assert_eq!(legacy_order(&words("-m --no-focus next")), words("next -m --no-focus"));
+    }
+
+    #[test]
+    fn legacy_order_keeps_the_subcommand_form() {
+        assert_eq!(legacy_order(&words("next -m")), words("next -m"));
+        assert_eq!(legacy_order(&words("-m 3")), words("-m 3"));
+    }
+
+    #[test]
+    fn legacy_order_skips_flag_values() {
+        assert_eq!(legacy_order(&words("--sock next prev")), words("prev --sock next"));
+        assert_eq!(legacy_order(&words("--create-on next next")), words("next --create-on next"));
+    }
+
+    #[test]
+    fn parse_takes_the_legacy_form() {
+        let args = parse(&words("-m -n prev")).unwrap();
+        assert!(matches!(args.action, Action::Prev));
+        assert!(args.move_ws && args.no_focus_ws);
+    }
+}
@@ -0,0 +1,118 @@
+use std::env::var;
+
+use clap::Command;
+use ksway::Client;
+
+use crate::state;
+
+
+const BASH: &str = r#"_sway_workspace() {
+    local IFS=$'\n'
+    COMPREPLY=($(sway-workspace __complete "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+}
+complete -o nosort -F _sway_workspace sway-workspace
+"#;
+
+const ZSH: &str = r#"#compdef sway-workspace
+_sway_workspace() {
+    local -a candidates
+    candidates=("${(@f)$(sway-workspace __complete "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    compadd -Q -a candidates
+}
+compdef _sway_workspace sway-workspace
+"#;
+
+const FISH: &str = r#"complete -c sway-workspace -f -a '(sway-workspace __complete (commandline -opc)[2..-1] (commandline -ct))'
+"#;
+
+/// Completion script for a shell, the script calls back into `__complete` for the candidates
+pub fn script(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" => Some(BASH),
+        "zsh" => Some(ZSH),
+        "fish" => Some(FISH),
+        _ => None,
+    }
+}
+
+fn workspace_names() -> Vec<String> {
+    let Ok(sock) = var("SWAYSOCK") else {
+        return Vec::new();
+    };
+    let Ok(mut client) = Client::connect_to_path(sock) else {
+        return Vec::new();
+    };
+    crate::get_workspaces(&mut client).unwrap_or_default().iter()
+        .filter_map(|w| w["name"].as_str().map(String::from))
+        .collect()
+}
+
+/// Candidates for the last of `words`, the command line after the program name
+pub fn candidates(command: &Command, words: &[String]) -> Vec<String> {
+    let (current, before) = match words.split_last() {
+        Some((current, before)) => (current.as_str(), before),
+        None => ("", words),
+    };
+
+    let takes_value = |word: &str| std::iter::once(command).chain(command.get_subcommands())
+        .flat_map(|c| c.get_arguments())
+        .any(|a| {
+            a.get_action().takes_values()
+                && (a.get_long().map(|l| format!("--{l}")).as_deref() == Some(word)
+                    || a.get_short().map(|s| format!("-{s}")).as_deref() == Some(word))
+        });
+
+    if before.last().is_some_and(|w| takes_value(w)) {
+        return Vec::new();
+    }
+
+    let mut positional: Vec<&str> = Vec::new();
+    let mut skip = false;
+    for word in before {
+        if skip {
+            skip = false;
+        } else if word.starts_with('-') {
+            skip = takes_value(word);
+        } else {
+            positional.push(word);
+        }
+    }
+
+    if positional.len() > 1 && positional[0] == "push" {
+        positional.remove(0);
+    }
+
+    // Flags of the subcommand, the older form has them before it
+    if current.starts_with('-') {
+        let subcommand = positional.first().and_then(|name| command.find_subcommand(name));
+        return command.get_arguments().chain(subcommand.into_iter().flat_map(|c| c.get_arguments()))
+            .filter(|a| !a.is_hide_set())
+            .filter_map(|a| a.get_long().map(|l| format!("--{l}")))
+            .filter(|f| f.starts_with(current))
+            .collect();
+    }
+
+    let actions = || -> Vec<String> {
+        command.get_subcommands()
+            .filter(|c| !c.is_hide_set())
+            .map(|c| c.get_name().to_string())
+            .collect()
+    };
+    let all: Vec<String> = match positional.as_slice() {
+        [] | ["push"] => actions(),
+        ["goto" | "goto-name"] => workspace_names(),
+        ["bookmark"] => vec!["set".to_string(), "go".to_string()],
+        ["bookmark", "go"] => state::load().bookmarks.into_keys().collect(),
+        ["set"] => vec!["activate".to_string()],
+        ["label"] => vec!["set".to_string(), "clear".to_string()],
+        ["query"] => vec!["prev-workspace".to_string()],
+        ["label", "set" | "clear"] => workspace_names(),
+        ["set", "activate"] => crate::config::load().sets.into_keys().collect(),
+        ["profile"] => vec!["activate".to_string(), "clear".to_string()],
+        ["profile", "activate"] => crate::config::load().profiles.into_keys().collect(),
+        ["open"] => crate::config::load().templates.into_keys().collect(),
+        ["completions"] => vec!["bash".to_string(), "zsh".to_string(), "fish".to_string()],
+        _ => Vec::new(),
+    };
+    all.into_iter().filter(|c| c.starts_with(current)).collect()
+}
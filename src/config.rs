@@ -0,0 +1,601 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env::var;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use std::process::exit;
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::output::{self, Output};
+use crate::tree::Node;
+use crate::{profile, state};
+
+
+/// Settings read from `$XDG_CONFIG_HOME/sway-workspace/config.toml`
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Sway/i3 socket path used when `--sock` is not given, before `$SWAYSOCK`
+    pub sock: Option<String>,
+
+    /// Workspace number offset per output, used by `sync-goto`. Here and everywhere else outputs can
+    /// also be given as `leftmost`, `rightmost`, `primary` or `internal`
+    pub offsets: HashMap<String, i64>,
+
+    pub daemon: DaemonConfig,
+
+    pub navigation: NavigationConfig,
+
+    /// Project directory per workspace name or number, printed by `cwd`
+    pub projects: HashMap<String, String>,
+
+    /// Named workspace sets mapping output names to workspaces, used by `set activate`
+    pub sets: BTreeMap<String, BTreeMap<String, WorkspaceSpec>>,
+
+    /// Output new workspaces are created on, per workspace name or number
+    pub create_on: HashMap<String, String>,
+
+    /// Workspace numbers each output owns: on-output actions stay inside them and new workspaces with
+    /// one of the numbers are created on the output
+    pub ranges: HashMap<String, NumberRange>,
+
+    pub numbering: NumberingConfig,
+
+    pub picker: PickerConfig,
+
+    pub archive: ArchiveConfig,
+
+    pub cursor: CursorConfig,
+
+    pub guard: GuardConfig,
+
+    pub spill: SpillConfig,
+
+    pub autoname: AutonameConfig,
+
+    pub hooks: HooksConfig,
+
+    pub wallpaper: WallpaperConfig,
+
+    pub empty: EmptyConfig,
+
+    pub display: DisplayConfig,
+
+    pub outputs: OutputsConfig,
+
+    pub rebalance: RebalanceConfig,
+
+    pub compact: CompactConfig,
+
+    /// Where workspaces go while an output is missing, applied by the daemon when outputs change
+    pub remap: Vec<RemapRule>,
+
+    /// Where the daemon moves new windows, the first rule that matches a window wins
+    pub rules: Vec<WindowRule>,
+
+    pub deck: DeckConfig,
+
+    /// Named profiles switched by time of day or by `profile activate`
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Apps `open <NAME>` starts together on a fresh workspace
+    pub templates: BTreeMap<String, Template>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Local time range like `09:00-17:00` the daemon activates the profile in, may run past midnight
+    pub hours: Option<String>,
+    /// Days of the week the hours apply to, `mon` to `sun`, every day when empty
+    pub days: Vec<String>,
+    /// Workspace set activated along with the profile
+    pub set: Option<String>,
+    /// Offsets per output replacing the top-level ones while the profile is active
+    pub offsets: HashMap<String, i64>,
+    /// Number ranges per output replacing the ones in `[ranges]` while the profile is active
+    pub ranges: HashMap<String, NumberRange>,
+    /// Workspaces pinned on top of `[compact] pinned` and `[rebalance] pinned` while the profile is active
+    pub compact_pinned: Vec<WorkspaceSpec>,
+    pub rebalance_pinned: Vec<WorkspaceSpec>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    /// Commands started with sway's `exec`, in order
+    pub apps: Vec<String>,
+    /// Sway commands run on the workspace after the apps, like `layout tabbed`
+    pub commands: Vec<String>,
+    /// Hold the commands back until every app has a window on the workspace
+    pub wait: bool,
+    /// Milliseconds to wait for the windows at most
+    pub timeout: u64,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template { apps: Vec::new(), commands: Vec::new(), wait: false, timeout: 5000 }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeckConfig {
+    /// Action line per button identifier, for `deck`
+    pub buttons: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct NumberRange {
+    pub first: i64,
+    pub last: i64,
+}
+
+impl NumberRange {
+    pub fn contains(&self, num: i64) -> bool {
+        (self.first..=self.last).contains(&num)
+    }
+}
+
+/// Workspaces `first` to `last` belong on `output`, and on `fallback` while it is gone
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RemapRule {
+    pub output: String,
+    pub first: i64,
+    pub last: i64,
+    pub fallback: String,
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let pattern: String = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// New windows matching the regular expressions, every one given, go to `workspace`, or to the first
+/// workspace without windows with `first_empty`, on `output` if given
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WindowRule {
+    #[serde(default, deserialize_with = "regex")]
+    pub app_id: Option<Regex>,
+    /// X11 class
+    #[serde(default, deserialize_with = "regex")]
+    pub class: Option<Regex>,
+    #[serde(default, deserialize_with = "regex")]
+    pub title: Option<Regex>,
+    #[serde(default)]
+    pub workspace: Option<WorkspaceSpec>,
+    #[serde(default)]
+    pub first_empty: bool,
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, window: &Node) -> bool {
+        let class: Option<&str> = window.window_properties.as_ref().and_then(|p| p.class.as_deref());
+        let matches = |pattern: &Option<Regex>, value: Option<&str>| pattern.as_ref().is_none_or(|p| value.is_some_and(|v| p.is_match(v)));
+        matches(&self.app_id, window.app_id.as_deref()) && matches(&self.class, class) && matches(&self.title, window.name.as_deref())
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RebalanceConfig {
+    /// Share of the workspaces per output name for `rebalance` and `balance`, outputs not listed weigh 1
+    pub weights: HashMap<String, u64>,
+    /// Workspaces `balance` leaves on their output, by number, name or `/regex/` on the name
+    pub pinned: Vec<WorkspaceSpec>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputsConfig {
+    /// Outputs in the order actions count and cycle through them, the ones left out follow in layout order
+    pub order: Vec<String>,
+    /// How the layout orders outputs
+    pub sort: OutputSort,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSort {
+    /// Left to right, outputs above each other top to bottom
+    #[default]
+    X,
+    /// Top to bottom, outputs next to each other left to right, for stacked monitors
+    Y,
+    /// Row by row from the top, the first left to right, the next right to left and so on
+    Serpentine,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberingConfig {
+    /// Let every output without a range own the numbers 1 to 9 after its offset, so 1 to 9 on the
+    /// first output, 11 to 19 on the second and so on
+    pub decades: bool,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompactConfig {
+    /// Workspaces `compact` leaves at their number, by number, name or `/regex/` on the name
+    pub pinned: Vec<WorkspaceSpec>,
+    /// Give every output a block of numbers of its own, like `--per-output`
+    pub per_output: bool,
+    /// Let the daemon compact whenever a workspace is created or goes away
+    pub auto: bool,
+}
+
+/// How workspace numbers are printed, commands sent to sway always use the numbers
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub numbers: Numbering,
+    /// Glyph for each number starting at 1, numbers past the list fall back to `numbers`
+    pub glyphs: Vec<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Numbering {
+    #[default]
+    Decimal,
+    Roman,
+    Letters,
+}
+
+/// Window limits the daemon keeps workspaces under by moving new windows on
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpillConfig {
+    /// Windows allowed on any workspace, 0 for no limit
+    pub max: usize,
+    /// Limits per workspace name or number, over `max`
+    pub workspaces: HashMap<String, usize>,
+}
+
+impl SpillConfig {
+    pub fn enabled(&self) -> bool {
+        self.max > 0 || self.workspaces.values().any(|l| *l > 0)
+    }
+
+    /// Window limit of a workspace, looked up by full name, then by number
+    pub fn limit(&self, name: &str, num: i64) -> Option<usize> {
+        self.workspaces.get(name)
+            .or_else(|| self.workspaces.get(&num.to_string()))
+            .copied()
+            .or(Some(self.max))
+            .filter(|l| *l > 0)
+    }
+}
+
+/// Commands run with `sh -c` around every switch to another workspace, with the workspaces and outputs in
+/// `SWAY_WORKSPACE_OLD`, `SWAY_WORKSPACE_NEW`, `SWAY_WORKSPACE_OLD_OUTPUT` and `SWAY_WORKSPACE_NEW_OUTPUT`
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Waited for, the switch is called off when it fails
+    pub before: Option<String>,
+    /// Started once the switch is sent, not waited for
+    pub after: Option<String>,
+}
+
+/// Background per workspace, put up by the daemon on the output a workspace is focused on
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WallpaperConfig {
+    pub tool: WallpaperTool,
+    /// How swaybg scales the image, `fill`, `fit`, `stretch`, `center` or `tile`
+    pub mode: String,
+    /// Image for workspaces not listed
+    pub default: Option<String>,
+    /// Image per workspace name or number
+    pub workspaces: HashMap<String, String>,
+}
+
+impl Default for WallpaperConfig {
+    fn default() -> Self {
+        WallpaperConfig { tool: WallpaperTool::Swaybg, mode: "fill".to_string(), default: None, workspaces: HashMap::new() }
+    }
+}
+
+impl WallpaperConfig {
+    pub fn enabled(&self) -> bool {
+        self.default.is_some() || !self.workspaces.is_empty()
+    }
+
+    /// Image of a workspace, looked up by full name, then by number
+    pub fn file(&self, name: &str, num: Option<i64>) -> Option<&str> {
+        self.workspaces.get(name)
+            .or_else(|| self.workspaces.get(&num?.to_string()))
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WallpaperTool {
+    Swaybg,
+    Swww,
+}
+
+/// Workspace names the daemon keeps up to date with the windows on them
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutonameConfig {
+    pub enabled: bool,
+    /// Glyph per app id, or class for X11 windows
+    pub icons: HashMap<String, String>,
+    /// Glyph for windows not in `icons`, their app id when not set
+    pub fallback: Option<String>,
+}
+
+impl AutonameConfig {
+    /// `num: <glyphs>` for the apps on a workspace, or just the number without windows
+    pub fn name<'a>(&self, num: i64, apps: impl IntoIterator<Item = &'a str>) -> String {
+        let mut glyphs: Vec<&str> = Vec::new();
+        for app in apps {
+            let glyph: &str = self.icons.get(app).or(self.fallback.as_ref()).map_or(app, String::as_str);
+            if !glyphs.contains(&glyph) {
+                glyphs.push(glyph);
+            }
+        }
+        if glyphs.is_empty() { num.to_string() } else { format!("{num}: {}", glyphs.join(" ")) }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct NavigationConfig {
+    /// Order `next` and `prev` step through the workspaces in, `--order` wins over it
+    pub order: Order,
+    /// Go round past the ends like `--wrap`, `--no-wrap` wins over it
+    pub wrap: bool,
+    /// Let `next-output` and `prev-output` go round from the last output to the first and back
+    pub wrap_outputs: bool,
+    /// Step `next` and `prev` through the workspaces output by output, like `--layout`
+    pub layout_aware: bool,
+    /// Workspaces navigation passes over, by number, name or `/regex/` on the name
+    pub skip: Vec<WorkspaceSpec>,
+}
+
+#[derive(Deserialize, ValueEnum, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// By number, the workspaces with only a name after the numbered ones
+    #[default]
+    Number,
+    /// By name in natural order, so `2:code` comes before `10:chat`
+    Name,
+}
+
+/// Windows that do not keep a workspace from counting as empty, like a sticky picture-in-picture
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmptyConfig {
+    /// Leave out sticky windows, sway carries them along to whichever workspace is visible
+    pub ignore_sticky: bool,
+    /// Leave out floating windows with one of these app ids, X11 classes or titles
+    pub ignore_floating: Vec<String>,
+}
+
+/// Switches that are refused unless `--force` is given
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuardConfig {
+    /// Stay on a workspace that shows a fullscreen window, e.g. during a presentation or screen share
+    pub fullscreen: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CursorConfig {
+    /// Command printing the pointer position as `X Y`, run with `sh -c` for `--under-cursor`
+    pub command: String,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        CursorConfig { command: "wl-find-cursor -p".to_string() }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    /// Seconds a workspace has to stay unfocused before the daemon archives it, 0 never archives
+    pub after: u64,
+    /// Prepended to the name of archived workspaces
+    pub prefix: String,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        ArchiveConfig { after: 0, prefix: "archive:".to_string() }
+    }
+}
+
+/// A workspace given by number or by name in the config
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum WorkspaceSpec {
+    Number(i64),
+    Name(String),
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PickerConfig {
+    /// dmenu-like command reading entries on stdin and printing the choice, run with `sh -c`
+    pub command: String,
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        PickerConfig { command: "dmenu".to_string() }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Milliseconds to wait for another digit before jumping
+    pub digit_timeout: u64,
+
+    /// Milliseconds after the last `mru-next` or `mru-prev` the cycle ends, unless `mru-commit` ends it first
+    pub mru_timeout: u64,
+
+    /// Milliseconds of quiet after a burst of sway events before it is handled
+    pub debounce: u64,
+
+    /// Actions run on POSIX signals, like `SIGUSR1 = "next-on-output"`
+    pub signals: BTreeMap<String, String>,
+
+    /// Named pipe to read action lines from, `true` for the default path
+    pub fifo: Option<Fifo>,
+
+    pub lock: LockConfig,
+
+    /// Control over HTTP, off unless configured
+    pub http: Option<HttpConfig>,
+
+    /// Record the workspaces with their outputs and bring their names and outputs back after sway restarts
+    pub session: bool,
+
+    /// Move workspaces sway parked on another output back to their own once it is connected again
+    pub return_home: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Address to listen on, keep it on localhost unless something in front of it adds TLS
+    #[serde(default = "HttpConfig::default_listen")]
+    pub listen: String,
+    /// Bearer token every request has to carry
+    pub token: String,
+}
+
+impl HttpConfig {
+    fn default_listen() -> String {
+        "127.0.0.1:7311".to_string()
+    }
+}
+
+/// What the daemon does with actions while the screen is locked
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockConfig {
+    /// Process names of screen lockers
+    pub processes: Vec<String>,
+    pub policy: LockPolicy,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        LockConfig {
+            processes: ["swaylock", "gtklock", "waylock", "hyprlock"].map(String::from).to_vec(),
+            policy: LockPolicy::Discard,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LockPolicy {
+    /// Run actions anyway
+    Run,
+    /// Drop actions that arrive while locked
+    Discard,
+    /// Queue actions and run them after unlocking
+    Replay,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Fifo {
+    Enabled(bool),
+    Path(PathBuf),
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            digit_timeout: 1000,
+            mru_timeout: 1000,
+            debounce: 100,
+            signals: BTreeMap::new(),
+            fifo: None,
+            lock: LockConfig::default(),
+            http: None,
+            session: false,
+            return_home: false,
+        }
+    }
+}
+
+impl Config {
+    /// Offset for the output at `index` in layout order, falling back to a block of ten per output
+    pub fn offset(&self, outputs: &[Output], index: usize) -> i64 {
+        let name: &str = outputs.get(index).map(|o| o.name.as_str()).unwrap_or_default();
+        match output::lookup(&self.offsets, outputs, name) {
+            Some(offset) => *offset,
+            None => index as i64 * 10,
+        }
+    }
+
+    /// The number ranges of the outputs, with `[numbering] decades` a decade for every output `[ranges]`
+    /// gives none
+    pub fn output_ranges(&self, outputs: &[Output]) -> HashMap<String, NumberRange> {
+        let mut ranges: HashMap<String, NumberRange> = self.ranges.to_owned();
+        if !self.numbering.decades {
+            return ranges;
+        }
+        for (index, output) in outputs.iter().enumerate() {
+            if output::lookup(&self.ranges, outputs, &output.name).is_none() {
+                let offset: i64 = self.offset(outputs, index);
+                ranges.insert(output.name.to_owned(), NumberRange { first: offset + 1, last: offset + 9 });
+            }
+        }
+        ranges
+    }
+}
+
+pub fn config_path() -> PathBuf {
+    let base = match var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(var("HOME").unwrap_or_default()).join(".config"),
+    };
+    base.join("sway-workspace").join("config.toml")
+}
+
+/// Read the config file as it is, a missing file yields the defaults
+pub fn read() -> Result<Config, String> {
+    let path = config_path();
+    let Ok(content) = read_to_string(&path) else {
+        return Ok(Config::default());
+    };
+    toml::from_str(&content).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// Load the config file with the active profile applied, exits when it does not parse
+pub fn load() -> Config {
+    match read() {
+        Ok(mut config) => {
+            profile::apply(&mut config, &state::load());
+            config
+        },
+        Err(err) => {
+            eprintln!("{err}");
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::process::Command;
+
+use crate::config::Config;
+use crate::output::Output;
+
+
+/// Pointer position in the global layout, asked from the configured helper since sway's IPC does not report it
+pub fn position(config: &Config) -> Result<(i64, i64), String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&config.cursor.command)
+        .output()
+        .map_err(|e| format!("{}: {e}", config.cursor.command))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut numbers = stdout.split(|c: char| !(c.is_ascii_digit() || c == '-')).filter_map(|n| n.parse().ok());
+    match (output.status.success(), numbers.next(), numbers.next()) {
+        (true, Some(x), Some(y)) => Ok((x, y)),
+        _ => Err(format!("{}: no pointer position in {:?}", config.cursor.command, stdout.trim())),
+    }
+}
+
+/// The output whose area contains the point
+pub fn output_at(outputs: &[Output], x: i64, y: i64) -> Option<&Output> {
+    outputs.iter().find(|o| (o.x..o.x + o.width).contains(&x) && (o.y..o.y + o.height).contains(&y))
+}
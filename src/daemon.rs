@@ -0,0 +1,1092 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{File, metadata, read_dir, remove_file};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ksway::{Client, ipc_command};
+use serde_json::{Value, from_slice, from_value, json};
+use signal_hook::consts::{SIGHUP, SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::config::{self, Config, EmptyConfig, Fifo, LockPolicy, WindowRule, WorkspaceSpec};
+use crate::{Error, archive, check_words, find_on_output, get_workspaces, http, ipc, lock, num_of, profile, quote, renumber, schema, tree};
+use crate::output::{self, Output, get_outputs};
+use crate::skip::Skip;
+use crate::wallpaper::Wallpapers;
+use crate::state::{self, runtime_dir};
+
+
+/// Something the daemon loop has to react to
+pub enum Event {
+    /// A line received on the named pipe
+    Command(String),
+    /// A line received on the control socket and where to say how it went
+    Request(String, Sender<Result<(), String>>),
+    /// A POSIX signal mapped to an action in the config
+    Signal(i32),
+    /// An event from the sway subscription
+    Sway(Kind, Value),
+    /// A request to the HTTP endpoint and where its response goes
+    Http(http::Request, Sender<http::Response>),
+    /// The subscription connection to sway is gone
+    Disconnected,
+}
+
+pub fn socket_path() -> PathBuf {
+    runtime_dir().join("sway-workspace.sock")
+}
+
+fn fifo_path(config: &Config) -> Option<PathBuf> {
+    match &config.daemon.fifo {
+        Some(Fifo::Enabled(true)) => Some(runtime_dir().join("sway-workspace.fifo")),
+        Some(Fifo::Path(path)) => Some(path.to_owned()),
+        Some(Fifo::Enabled(false)) | None => None,
+    }
+}
+
+/// Send a command line to a running daemon
+pub fn send(command: &str) -> std::io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.write_all(format!("{command}\n").as_bytes())
+}
+
+/// Send a command line to a running daemon and wait for how it went, `ok` or `error <MESSAGE>`. Fails
+/// only when no daemon takes the line, once it has the line the daemon is the one to run it
+pub fn request(command: &str) -> std::io::Result<Result<(), String>> {
+    let stream = UnixStream::connect(socket_path())?;
+    (&stream).write_all(format!("{command}\n").as_bytes())?;
+    let mut reply = String::new();
+    let _ = BufReader::new(&stream).read_line(&mut reply);
+    Ok(match reply.trim_end().strip_prefix("error ") {
+        Some(message) => Err(message.to_string()),
+        None => Ok(()),
+    })
+}
+
+/// Take over the control socket, refusing while another daemon answers on it, and read every
+/// connection on a thread of its own so that a client keeping its connection open holds up nobody
+fn listen(tx: Sender<Event>) -> Result<(), Error> {
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        return Err(Error::Failed(format!("a daemon is running already, {} answers", path.display())));
+    }
+    // Nobody answers on a socket a daemon that went away left behind
+    let _ = remove_file(&path);
+    let listener = UnixListener::bind(&path).map_err(|e| Error::Failed(format!("{}: {e}", path.display())))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(&stream).lines().map_while(Result::ok) {
+                    let (done_tx, done) = channel();
+                    if tx.send(Event::Request(line.to_owned(), done_tx)).is_err() {
+                        return;
+                    }
+                    let failed: Option<String> = done.recv().ok().and_then(Result::err);
+                    let reply: String = match &failed {
+                        Some(message) => format!("error {}\n", message.replace('\n', " ")),
+                        None => "ok\n".to_string(),
+                    };
+                    // Senders that do not wait for the reply have hung up already, what failed goes to the log
+                    if let (Err(_), Some(message)) = ((&stream).write_all(reply.as_bytes()), failed) {
+                        eprintln!("{line}: {message}");
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Read action lines from the configured named pipe, creating it when missing
+fn read_fifo(config: &Config, tx: Sender<Event>) {
+    let Some(path) = fifo_path(config) else {
+        return;
+    };
+
+    match metadata(&path) {
+        Ok(meta) if meta.file_type().is_fifo() => {},
+        Ok(_) => {
+            eprintln!("{} exists and is not a named pipe", path.display());
+            exit(1);
+        },
+        Err(_) => {
+            let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                eprintln!("cannot create {}: {}", path.display(), std::io::Error::last_os_error());
+                exit(1);
+            }
+        },
+    }
+
+    thread::spawn(move || {
+        // Opening blocks until a writer shows up, reopen after every writer is done
+        while let Ok(fifo) = File::open(&path) {
+            for line in BufReader::new(fifo).lines().map_while(Result::ok) {
+                if tx.send(Event::Command(line)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Sway events the daemon subscribes to
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Workspace,
+    Output,
+    Window,
+}
+
+impl Kind {
+    fn from_type(message_type: u32) -> Option<Kind> {
+        match message_type {
+            0x80000000 => Some(Kind::Workspace),
+            0x80000001 => Some(Kind::Output),
+            0x80000003 => Some(Kind::Window),
+            _ => None,
+        }
+    }
+}
+
+/// Read one i3-ipc message, returns its type and payload
+fn read_message(stream: &mut UnixStream) -> std::io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((message_type, payload))
+}
+
+/// Forward sway events from a second connection, the first one stays free for requests. The subscription
+/// is spoken directly since ksway does not know output events
+pub fn subscribe(client: &Client, tx: Sender<Event>) {
+    const SUBSCRIBE: u32 = 2;
+    let payload: &[u8] = br#"["workspace","output","window"]"#;
+    let mut stream = UnixStream::connect(client.socket_path()).unwrap();
+    let mut message: Vec<u8> = b"i3-ipc".to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(SUBSCRIBE.to_ne_bytes());
+    message.extend(payload);
+    stream.write_all(&message).unwrap();
+
+    thread::spawn(move || {
+        loop {
+            let Ok((message_type, payload)) = read_message(&mut stream) else {
+                let _ = tx.send(Event::Disconnected);
+                return;
+            };
+            let (Some(kind), Ok(payload)) = (Kind::from_type(message_type), from_slice(&payload)) else {
+                continue;
+            };
+            if tx.send(Event::Sway(kind, payload)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Signal number for a name like `SIGUSR1`, `USR2` or `SIGRTMIN+3`
+fn signal_number(name: &str) -> Option<i32> {
+    let name = name.strip_prefix("SIG").unwrap_or(name);
+    match name {
+        "USR1" => Some(SIGUSR1),
+        "USR2" => Some(SIGUSR2),
+        "HUP" => Some(SIGHUP),
+        _ => {
+            let offset: i32 = match name.strip_prefix("RTMIN") {
+                Some("") => 0,
+                Some(offset) => offset.strip_prefix('+')?.parse().ok()?,
+                None => return None,
+            };
+            let signal = libc::SIGRTMIN() + offset;
+            (signal <= libc::SIGRTMAX()).then_some(signal)
+        },
+    }
+}
+
+/// Register the configured signals and SIGHUP, which reloads the config unless it has an action,
+/// exits when a signal name or action is invalid
+fn handle_signals(config: &Config, tx: Sender<Event>) -> HashMap<i32, Vec<String>> {
+    let mut actions: HashMap<i32, Vec<String>> = HashMap::new();
+    for (name, action) in &config.daemon.signals {
+        let Some(signal) = signal_number(name) else {
+            eprintln!("unknown signal {name}");
+            exit(1);
+        };
+        let words: Vec<String> = match shell_words::split(action) {
+            Ok(words) => words,
+            Err(err) => {
+                eprintln!("{name}: {err}");
+                exit(1);
+            },
+        };
+        if let Err(err) = crate::check_words(&words) {
+            eprintln!("{name}: {err}");
+            exit(1);
+        }
+        actions.insert(signal, words);
+    }
+
+    let mut signals = Signals::new(actions.keys().copied().chain([SIGHUP])).unwrap();
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if tx.send(Event::Signal(signal)).is_err() {
+                return;
+            }
+        }
+    });
+
+    actions
+}
+
+/// Digits typed so far and when they get committed
+struct Digits {
+    typed: String,
+    deadline: Option<Instant>,
+}
+
+impl Digits {
+    fn push(&mut self, digit: &str, timeout: Duration) {
+        self.typed.push_str(digit);
+        self.deadline = Some(Instant::now() + timeout);
+    }
+
+    fn take(&mut self) -> Option<i64> {
+        self.deadline = None;
+        let typed = std::mem::take(&mut self.typed);
+        typed.parse().ok()
+    }
+}
+
+/// Workspace names by last focus, most recent first, and the cycle `mru next` and `mru prev` are in
+struct Mru {
+    order: Vec<String>,
+    /// The order when the cycle started, and the position in it
+    cycle: Vec<String>,
+    position: usize,
+    deadline: Option<Instant>,
+}
+
+impl Mru {
+    fn focused(&mut self, name: &str) {
+        self.order.retain(|n| n != name);
+        self.order.insert(0, name.to_string());
+    }
+
+    /// The workspace `step` away in the cycle, which starts from the existing workspaces in MRU order
+    fn step(&mut self, workspaces: &[Value], step: i64, timeout: Duration) -> Option<String> {
+        if self.deadline.is_none() {
+            let names: Vec<&str> = workspaces.iter().filter_map(|w| w["name"].as_str()).collect();
+            if let Some(focused) = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str()) {
+                self.focused(focused);
+            }
+            self.cycle = self.order.iter().filter(|n| names.contains(&n.as_str())).cloned()
+                .chain(names.iter().filter(|n| !self.order.iter().any(|o| o == *n)).map(|n| n.to_string()))
+                .collect();
+            self.position = 0;
+        }
+        if self.cycle.len() < 2 {
+            return None;
+        }
+        self.position = (self.position as i64 + step).rem_euclid(self.cycle.len() as i64) as usize;
+        self.deadline = Some(Instant::now() + timeout);
+        Some(self.cycle[self.position].to_owned())
+    }
+
+    /// End the cycle, the workspace it stopped at becomes the most recent one
+    fn commit(&mut self) {
+        self.deadline = None;
+        if let Some(name) = self.cycle.get(self.position).cloned() {
+            self.focused(&name);
+        }
+        self.cycle.clear();
+    }
+}
+
+/// Work the daemon does on behalf of a trigger
+enum Job {
+    /// An action given as command line words
+    Action(Vec<String>),
+    /// A raw sway command
+    Sway(String),
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Job::Action(words) => write!(f, "{}", words.join(" ")),
+            Job::Sway(command) => write!(f, "{command}"),
+        }
+    }
+}
+
+/// How often the lock state is checked while actions are waiting for an unlock
+const LOCK_POLL: Duration = Duration::from_millis(500);
+
+/// Holds jobs back while the screen is locked
+struct LockGate {
+    queued: Vec<Job>,
+    next_check: Option<Instant>,
+}
+
+/// Longest a burst of sway events is held back, even when events keep coming
+const MAX_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Sway events collected until sway has settled
+struct Burst {
+    events: Vec<(Kind, Value)>,
+    started: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl Burst {
+    fn push(&mut self, kind: Kind, payload: Value, debounce: Duration) {
+        let now = Instant::now();
+        let started: Instant = *self.started.get_or_insert(now);
+        self.deadline = Some((now + debounce).min(started + MAX_DEBOUNCE));
+        self.events.push((kind, payload));
+    }
+
+    /// The collected events with only the last one of each change to the same container kept
+    fn take(&mut self) -> Vec<(Kind, Value)> {
+        self.started = None;
+        self.deadline = None;
+        let key = |(kind, payload): &(Kind, Value)| {
+            let id: &Value = if payload["current"].is_null() { &payload["container"]["id"] } else { &payload["current"]["id"] };
+            (*kind, payload["change"].to_owned(), id.to_owned())
+        };
+        let mut events = std::mem::take(&mut self.events);
+        let mut kept: Vec<(Kind, Value)> = Vec::new();
+        while let Some(event) = events.pop() {
+            if !kept.iter().any(|k| key(k) == key(&event)) {
+                kept.push(event);
+            }
+        }
+        kept.reverse();
+        kept
+    }
+}
+
+/// Archives workspaces that stayed unfocused for too long
+struct Archiver {
+    /// When each workspace, by container id, was last focused or first seen
+    last_focused: HashMap<i64, Instant>,
+    next_check: Option<Instant>,
+}
+
+struct Daemon<'a> {
+    client: &'a mut Client,
+    config: Config,
+    signal_actions: HashMap<i32, Vec<String>>,
+    digits: Digits,
+    mru: Mru,
+    lock: LockGate,
+    burst: Burst,
+    archiver: Archiver,
+    schedule: Schedule,
+    wallpapers: Wallpapers,
+    /// Output each workspace lives on by name, kept while sway parks it on another one
+    homes: HashMap<String, String>,
+}
+
+/// The profile the schedule picked last, the daemon steps in when that changes
+struct Schedule {
+    current: Option<String>,
+    next_check: Option<Instant>,
+}
+
+impl Daemon<'_> {
+    /// The earliest moment a timer needs attention
+    fn deadline(&self) -> Option<Instant> {
+        [self.digits.deadline, self.mru.deadline, self.lock.next_check, self.burst.deadline, self.archiver.next_check, self.schedule.next_check]
+            .into_iter().flatten().min()
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Command(line) => {
+                report(&line, self.handle_command(&line));
+            },
+            Event::Request(line, done) => {
+                let _ = done.send(self.handle_command(&line).map_err(|err| err.to_string().trim_end().to_string()));
+            },
+            Event::Signal(signal) => {
+                if let Some(words) = self.signal_actions.get(&signal) {
+                    self.submit(Job::Action(words.to_owned()));
+                } else if signal == SIGHUP {
+                    self.reload();
+                }
+            },
+            Event::Sway(kind, payload) => {
+                self.burst.push(kind, payload, Duration::from_millis(self.config.daemon.debounce));
+            },
+            Event::Http(request, reply) => {
+                let response: http::Response = self.handle_http(&request);
+                let _ = reply.send(response);
+            },
+            Event::Disconnected => {},
+        }
+    }
+
+    /// `GET /workspaces` lists the workspaces, `POST /workspaces/<NAME>` switches to one and
+    /// `POST /action` runs the action line in the body
+    fn handle_http(&mut self, request: &http::Request) -> http::Response {
+        let route: Vec<&str> = request.path.trim_matches('/').splitn(2, '/').collect();
+        match (request.method.as_str(), route.as_slice()) {
+            ("GET", ["workspaces"]) => match get_workspaces(self.client) {
+                Ok(workspaces) => {
+                    let state = state::load();
+                    let workspaces: Vec<Value> = workspaces.iter().map(|w| schema::workspace(&self.config, &state, w)).collect();
+                    http::Response::new(200, schema::document(json!({ "workspaces": workspaces })).to_string())
+                },
+                Err(err) => http::Response::new(502, json!({ "error": err.to_string() }).to_string()),
+            },
+            ("POST", ["workspaces", name]) if !name.is_empty() => {
+                self.submit(Job::Action(vec!["goto-name".to_string(), name.to_string()]));
+                http::Response::new(202, "{}")
+            },
+            ("POST", ["action"]) => {
+                let checked = shell_words::split(&request.body)
+                    .map_err(|e| e.to_string())
+                    .and_then(|words| check_words(&words).map_err(|e| e.to_string()));
+                match checked {
+                    Ok(()) => {
+                        report(&request.body, self.handle_command(&request.body));
+                        http::Response::new(202, "{}")
+                    },
+                    Err(err) => http::Response::new(400, json!({ "error": err.trim() }).to_string()),
+                }
+            },
+            _ => http::Response::new(404, r#"{"error":"no such route"}"#),
+        }
+    }
+
+    /// Handle a burst of sway events once sway has settled
+    fn settle(&mut self) {
+        let events = self.burst.take();
+        let outputs_changed: bool = events.iter().any(|(kind, _)| *kind == Kind::Output);
+        if outputs_changed && !self.config.remap.is_empty() {
+            report("remap", self.remap());
+        }
+        if self.config.daemon.return_home {
+            report("return home", self.return_home(outputs_changed));
+        }
+        // What sway shows after a reload is its default, not something to remember
+        let reloaded: bool = events.iter().any(|(kind, payload)| *kind == Kind::Workspace && payload["change"] == "reload");
+        if reloaded {
+            report("restore shown workspaces", self.restore_shown());
+        }
+        // Workspaces coming and going leave gaps in the numbering
+        let compacts: bool = self.config.compact.auto && events.iter()
+            .any(|(kind, payload)| *kind == Kind::Workspace && (payload["change"] == "init" || payload["change"] == "empty"));
+        let records: bool = self.config.daemon.session && events.iter().any(|(kind, _)| matches!(kind, Kind::Workspace | Kind::Output));
+        // Windows coming, going and moving change what the workspaces hold
+        let names: bool = self.config.autoname.enabled && events.iter()
+            .any(|(kind, payload)| *kind == Kind::Window && ["new", "close", "move"].iter().any(|c| payload["change"] == *c));
+        for (kind, payload) in events {
+            match kind {
+                Kind::Workspace if !reloaded => self.handle_workspace(&payload),
+                // A rule that could not be followed keeps the window where it is
+                Kind::Window if payload["change"] == "new" && !self.config.rules.is_empty()
+                    && report("rules", self.follow_rules(&payload)).unwrap_or(true) => {},
+                Kind::Window if payload["change"] == "new" && self.config.spill.enabled() => {
+                    report("spill", self.spill(&payload));
+                },
+                _ => {},
+            }
+        }
+        if names {
+            report("autoname", self.autoname());
+        }
+        if compacts {
+            report("compact", self.compact());
+        }
+        if records {
+            report("session", self.record_session());
+        }
+    }
+
+    /// Remember the workspaces there are and their outputs for after a restart of sway
+    fn record_session(&mut self) -> Result<(), Error> {
+        let session: Vec<state::WorkspaceRef> = get_workspaces(self.client)?.iter().map(state::WorkspaceRef::from_value).collect();
+        state::update(|state| state.session = session).map_err(Error::Failed)
+    }
+
+    /// Give the workspaces sway made after restarting the names they had, put them on their outputs
+    /// again and assign every recorded name to its output, so the workspaces created later land there
+    fn restore_session(&mut self) -> Result<(), Error> {
+        let recorded: Vec<state::WorkspaceRef> = state::load().session;
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+        let outputs: Vec<Output> = get_outputs(self.client, &self.config.outputs)?;
+        let recorded_name = |w: &Value| recorded.iter().any(|r| w["name"] == r.name.as_str());
+
+        let mut renames: Vec<renumber::Rename> = Vec::new();
+        let mut moves: Vec<String> = Vec::new();
+        let mut assigns: Vec<String> = Vec::new();
+        for workspace in &recorded {
+            let known: bool = outputs.iter().any(|o| o.name == workspace.output);
+            if known {
+                assigns.push(format!("workspace {} output {}", quote(&workspace.name), quote(&workspace.output)));
+            }
+            // sway names a workspace after its number, one without a recorded name takes the old name back
+            let existing: Option<&Value> = workspaces.iter().find(|w| w["name"] == workspace.name.as_str())
+                .or_else(|| workspaces.iter().find(|w| workspace.num >= 0 && num_of(w) == Some(workspace.num) && !recorded_name(w)));
+            let Some(existing) = existing else {
+                continue;
+            };
+            if let Some(name) = existing["name"].as_str().filter(|n| *n != workspace.name) {
+                renames.push((name.to_string(), workspace.name.to_owned()));
+            }
+            if known && existing["output"] != workspace.output.as_str() {
+                moves.push(format!("workspace {}; move workspace to output {}", quote(&workspace.name), quote(&workspace.output)));
+            }
+        }
+        if let (false, Some(focused)) = (moves.is_empty(), workspaces.iter().find(|w| w["focused"] == true)) {
+            let name: &str = focused["name"].as_str().unwrap_or_default();
+            moves.push(format!("workspace {}", quote(renames.iter().find(|(from, _)| from == name).map_or(name, |(_, to)| to))));
+        }
+
+        let commands: Vec<String> = assigns.into_iter().chain(renumber::commands(&renames)).chain(moves).collect();
+        if !commands.is_empty() {
+            self.submit(Job::Sway(commands.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Name every numbered workspace after the apps on it, keeping its number
+    fn autoname(&mut self) -> Result<(), Error> {
+        let tree: tree::Node = tree::get_tree(self.client)?;
+        let renames: Vec<renumber::Rename> = get_workspaces(self.client)?.iter()
+            .filter_map(|w| {
+                let (name, num) = (w["name"].as_str()?, num_of(w)?);
+                let windows = tree::find_workspace(&tree, name).map(tree::windows).unwrap_or_default();
+                let apps = windows.iter().filter_map(|(window, _)| schema::app_id(window));
+                Some((name.to_string(), self.config.autoname.name(num, apps)))
+            })
+            .collect();
+        let commands: Vec<String> = renumber::commands(&renames);
+        if !commands.is_empty() {
+            self.submit(Job::Sway(commands.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Number the workspaces without gaps again, like `compact`
+    fn compact(&mut self) -> Result<(), Error> {
+        // The pins and ranges of the active profile count as they do for `compact`
+        let mut config: Config = self.config.clone();
+        profile::apply(&mut config, &state::load());
+        let pinned: Skip = Skip::new(&config.compact.pinned).map_err(Error::Failed)?;
+        let outputs: Vec<Output> = if config.compact.per_output { get_outputs(self.client, &config.outputs)? } else { Vec::new() };
+        let renames: Vec<renumber::Rename> = renumber::compact(
+            &get_workspaces(self.client)?, &outputs, &config.output_ranges(&outputs), &pinned, config.compact.per_output,
+        );
+        let commands: Vec<String> = renumber::commands(&renames);
+        if !commands.is_empty() {
+            self.submit(Job::Sway(commands.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Put the workspaces of the remap rules on their output, or its fallback while it is missing
+    fn remap(&mut self) -> Result<(), Error> {
+        let outputs: Vec<Output> = get_outputs(self.client, &self.config.outputs)?;
+        let active = |reference: &str| output::resolve(&outputs, reference).map(|o| o.name.as_str());
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+
+        let mut commands: Vec<String> = Vec::new();
+        for rule in &self.config.remap {
+            let Some(target) = active(&rule.output).or_else(|| active(&rule.fallback)) else {
+                continue;
+            };
+            for workspace in &workspaces {
+                let in_range: bool = workspace["num"].as_i64().is_some_and(|n| (rule.first..=rule.last).contains(&n));
+                if let (true, Some(name)) = (in_range && workspace["output"] != target, workspace["name"].as_str()) {
+                    commands.push(format!("workspace {}; move workspace to output {}", quote(name), quote(target)));
+                }
+            }
+        }
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+        if let Some(focused) = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str()) {
+            commands.push(format!("workspace {}", quote(focused)));
+        }
+        self.submit(Job::Sway(commands.join("; ")));
+        Ok(())
+    }
+
+    /// Keep track of the output every workspace lives on, and once the outputs changed move the
+    /// workspaces sway parked elsewhere when their output went away back to it. A workspace moved while
+    /// its output is there makes the new one its home, the remap rules have the last word on theirs
+    fn return_home(&mut self, outputs_changed: bool) -> Result<(), Error> {
+        let outputs: Vec<Output> = get_outputs(self.client, &self.config.outputs)?;
+        let connected = |name: &str| outputs.iter().any(|o| o.name == name);
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+        let remapped = |w: &Value| self.config.remap.iter().any(|r| num_of(w).is_some_and(|n| (r.first..=r.last).contains(&n)));
+        self.homes.retain(|name, _| workspaces.iter().any(|w| w["name"] == name.as_str()));
+
+        let mut commands: Vec<String> = Vec::new();
+        for workspace in workspaces.iter().filter(|w| !remapped(w)) {
+            let (Some(name), Some(output)) = (workspace["name"].as_str(), workspace["output"].as_str()) else {
+                continue;
+            };
+            match self.homes.get(name) {
+                Some(home) if home != output && connected(home) && outputs_changed => {
+                    commands.push(format!("workspace {}; move workspace to output {}", quote(name), quote(home)));
+                },
+                Some(home) if !connected(home) => {},
+                _ => {
+                    self.homes.insert(name.to_string(), output.to_string());
+                },
+            }
+        }
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+        if let Some(focused) = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str()) {
+            commands.push(format!("workspace {}", quote(focused)));
+        }
+        self.submit(Job::Sway(commands.join("; ")));
+        Ok(())
+    }
+
+    /// Show the workspaces the outputs showed before sway reloaded or restarted, the focused one last
+    fn restore_shown(&mut self) -> Result<(), Error> {
+        let state = state::load();
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+        let focused: Option<&String> = state.focused_output.as_ref();
+        let restored: Vec<&Value> = state.shown.iter()
+            .filter(|(output, _)| Some(*output) != focused)
+            .chain(focused.and_then(|o| state.shown.get_key_value(o)))
+            .filter_map(|(output, shown)| shown.resolve(&workspaces).filter(|w| w["output"] == output.as_str()))
+            .collect();
+        if restored.iter().all(|w| w["visible"] == true) && restored.last().is_none_or(|w| w["focused"] == true) {
+            return Ok(());
+        }
+
+        let commands: Vec<String> = restored.iter()
+            .map(|w| format!("workspace --no-auto-back-and-forth {}", quote(w["name"].as_str().unwrap_or_default())))
+            .collect();
+        self.submit(Job::Sway(commands.join("; ")));
+        Ok(())
+    }
+
+    /// Move a new window where the first rule matching it says, false when none matches
+    fn follow_rules(&mut self, event: &Value) -> Result<bool, Error> {
+        let Ok(window) = from_value::<tree::Node>(event["container"].to_owned()) else {
+            return Ok(false);
+        };
+        let Some(rule) = self.config.rules.iter().find(|r| r.matches(&window)) else {
+            return Ok(false);
+        };
+        let outputs: Vec<Output> = if rule.output.is_some() { get_outputs(self.client, &self.config.outputs)? } else { Vec::new() };
+        let output: Option<&str> = rule.output.as_deref().map(|r| output::resolve(&outputs, r).map_or(r, |o| o.name.as_str()));
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+        let tree: tree::Node = tree::get_tree(self.client)?;
+
+        let Some((target, existing)) = rule_target(rule, output, &workspaces, &tree, &self.config.empty) else {
+            return Ok(true);
+        };
+        if existing.is_some_and(|w| tree::workspace_of(&tree, window.id).is_some_and(|t| w["id"] == t.id)) {
+            return Ok(true);
+        }
+        let mut commands: Vec<String> = vec![format!("[con_id={}] move container to workspace {target}", window.id)];
+        if let Some(output) = output.filter(|o| existing.is_none_or(|w| w["output"] != *o)) {
+            commands.push(format!("workspace {target}; move workspace to output {}", quote(output)));
+            if let Some(focused) = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str()) {
+                commands.push(format!("workspace {}", quote(focused)));
+            }
+        }
+        self.submit(Job::Sway(commands.join("; ")));
+        Ok(true)
+    }
+
+    /// Move a new window on to the next workspace of the output that is below its limit
+    fn spill(&mut self, event: &Value) -> Result<(), Error> {
+        let Some(id) = event["container"]["id"].as_i64() else {
+            return Ok(());
+        };
+        let tree: tree::Node = tree::get_tree(self.client)?;
+        let Some(workspace) = tree::workspace_of(&tree, id) else {
+            return Ok(());
+        };
+        let workspaces: Vec<Value> = get_workspaces(self.client)?;
+        let Some(current) = workspaces.iter().find(|w| w["id"] == workspace.id) else {
+            return Ok(());
+        };
+        let (Some(mut num), Some(output)) = (num_of(current), current["output"].as_str()) else {
+            return Ok(());
+        };
+        let full = |name: &str, num: i64, windows: usize| {
+            self.config.spill.limit(name, num).is_some_and(|limit| windows > limit)
+        };
+        if !full(workspace.name.as_deref().unwrap_or_default(), num, tree::count_occupying(workspace, &self.config.empty)) {
+            return Ok(());
+        }
+
+        loop {
+            let next: i64 = find_on_output(&workspaces, num, 1, output, &[]);
+            if next == num {
+                return Ok(());
+            }
+            num = next;
+            let Some(name) = workspaces.iter().find(|w| w["num"] == num).and_then(|w| w["name"].as_str()) else {
+                break;
+            };
+            let windows: usize = tree::find_workspace(&tree, name).map(|w| tree::count_occupying(w, &self.config.empty)).unwrap_or_default();
+            if !full(name, num, windows + 1) {
+                break;
+            }
+        }
+        self.submit(Job::Sway(format!("[con_id={id}] move container to workspace number {num}")));
+        Ok(())
+    }
+
+    fn handle_workspace(&mut self, event: &Value) {
+        let now = Instant::now();
+        for key in ["current", "old"] {
+            if let Some(id) = event[key]["id"].as_i64() {
+                self.archiver.last_focused.insert(id, now);
+            }
+        }
+        let (old, current) = (&event["old"], &event["current"]);
+        if event["change"] == "focus" {
+            let recorded = state::update(|state| {
+                if old.is_object() && old["id"] != current["id"] {
+                    state.record_switch(old, current["output"].as_str().unwrap_or_default());
+                    state.record_history(old, current);
+                }
+                state.record_shown(current);
+            });
+            report("focus", recorded);
+            if self.config.wallpaper.enabled() {
+                self.wallpapers.show(&self.config, current);
+            }
+            // Switches of a cycle in progress leave the order alone until it is committed
+            if self.mru.deadline.is_none() {
+                if let Some(name) = current["name"].as_str() {
+                    self.mru.focused(name);
+                }
+            }
+        }
+        if event["change"] == "empty" {
+            if let Some(id) = event["current"]["id"].as_i64() {
+                self.archiver.last_focused.remove(&id);
+            }
+        }
+    }
+
+    /// Archive the workspaces that were not focused within the configured period
+    fn archive_idle(&mut self) -> Result<(), Error> {
+        let after = Duration::from_secs(self.config.archive.after);
+        let now = Instant::now();
+        let commands: Vec<String> = get_workspaces(self.client)?.iter()
+            .filter(|w| w["visible"] != true && !archive::is_archived(&self.config, w))
+            .filter(|w| {
+                let seen = *self.archiver.last_focused.entry(w["id"].as_i64().unwrap_or(-1)).or_insert(now);
+                now.duration_since(seen) >= after
+            })
+            .map(|w| archive::archive_command(&self.config, w))
+            .collect();
+
+        if !commands.is_empty() {
+            self.submit(Job::Sway(commands.join("; ")));
+        }
+        Ok(())
+    }
+
+    fn handle_command(&mut self, line: &str) -> Result<(), Error> {
+        let words: Vec<String> = shell_words::split(line).map_err(|e| Error::Failed(e.to_string()))?;
+        match words.as_slice() {
+            [] => Ok(()),
+            [digit, d] if digit == "digit" && d.len() == 1 && d.chars().all(|c| c.is_ascii_digit()) => {
+                self.digits.push(d, Duration::from_millis(self.config.daemon.digit_timeout));
+                Ok(())
+            },
+            [mru, op] if mru == "mru" && (op == "next" || op == "prev") => {
+                let workspaces: Vec<Value> = get_workspaces(self.client)?;
+                let step: i64 = if op == "next" { 1 } else { -1 };
+                match self.mru.step(&workspaces, step, Duration::from_millis(self.config.daemon.mru_timeout)) {
+                    Some(name) => self.dispatch(Job::Sway(format!("workspace --no-auto-back-and-forth {}", quote(&name)))),
+                    None => Ok(()),
+                }
+            },
+            [mru, commit] if mru == "mru" && commit == "commit" => {
+                self.mru.commit();
+                Ok(())
+            },
+            _ => self.dispatch(Job::Action(words)),
+        }
+    }
+
+    /// Fire the timers that are due
+    fn tick(&mut self) {
+        let now = Instant::now();
+
+        if self.digits.deadline.is_some_and(|d| d <= now) {
+            if let Some(num) = self.digits.take() {
+                self.submit(Job::Sway(format!("workspace number {num}")));
+            }
+        }
+
+        if self.mru.deadline.is_some_and(|d| d <= now) {
+            self.mru.commit();
+        }
+
+        if self.burst.deadline.is_some_and(|d| d <= now) {
+            self.settle();
+        }
+
+        if self.archiver.next_check.is_some_and(|d| d <= now) {
+            // Look at the workspaces only once sway has settled
+            if self.burst.deadline.is_some() {
+                self.archiver.next_check = self.burst.deadline;
+            } else {
+                self.archiver.next_check = Some(now + archive_interval(&self.config));
+                report("archive", self.archive_idle());
+            }
+        }
+
+        if self.schedule.next_check.is_some_and(|d| d <= now) {
+            self.schedule.next_check = Some(now + PROFILE_POLL);
+            self.follow_schedule();
+        }
+
+        if self.lock.next_check.is_some_and(|d| d <= now) {
+            if lock::is_locked(&self.config.daemon.lock.processes) {
+                self.lock.next_check = Some(now + LOCK_POLL);
+            } else {
+                self.lock.next_check = None;
+                for job in std::mem::take(&mut self.lock.queued) {
+                    let what: String = job.to_string();
+                    report(&what, self.perform(job));
+                }
+            }
+        }
+    }
+
+    /// Switch to the scheduled profile once its hours begin, which also ends a profile activated by hand
+    fn follow_schedule(&mut self) {
+        let scheduled: Option<String> = profile::scheduled(&self.config).map(String::from);
+        if scheduled == self.schedule.current {
+            return;
+        }
+        self.schedule.current = scheduled.to_owned();
+
+        report("profile", state::update(|state| state.profile = None));
+        if let Some(set) = scheduled.and_then(|p| self.config.profiles.get(&p)?.set.to_owned()) {
+            self.submit(Job::Action(vec!["set".to_string(), "activate".to_string(), set]));
+        }
+    }
+
+    /// Run a job now, or hold it back according to the lock policy, logging what fails
+    fn submit(&mut self, job: Job) {
+        let what: String = job.to_string();
+        report(&what, self.dispatch(job));
+    }
+
+    /// Run a job now, or hold it back according to the lock policy. Only a job run now can fail
+    fn dispatch(&mut self, job: Job) -> Result<(), Error> {
+        let policy: LockPolicy = self.config.daemon.lock.policy;
+        if policy == LockPolicy::Run || !lock::is_locked(&self.config.daemon.lock.processes) {
+            return self.perform(job);
+        }
+        if policy == LockPolicy::Replay {
+            self.lock.queued.push(job);
+            self.lock.next_check.get_or_insert(Instant::now() + LOCK_POLL);
+        }
+        Ok(())
+    }
+
+    /// Read the config file again, keeping the one there is when the file does not parse. The signals,
+    /// the named pipe and the HTTP endpoint stay as they were set up at start
+    fn reload(&mut self) {
+        match config::read() {
+            Ok(config) => self.config = config,
+            Err(err) => eprintln!("keeping the config read before, {}", err.trim_end()),
+        }
+    }
+
+    fn perform(&mut self, job: Job) -> Result<(), Error> {
+        match job {
+            Job::Action(words) => crate::run_words(self.client, &self.config, &words),
+            Job::Sway(command) => {
+                let reply: Vec<u8> = match ipc(self.client, ipc_command::run(command.to_owned())) {
+                    Ok(reply) => reply,
+                    // The socket failed, try once more on a new connection before the subscription notices
+                    Err(err) => {
+                        let path: PathBuf = self.client.socket_path().to_path_buf();
+                        let Ok(client) = Client::connect_to_path(path) else {
+                            return Err(err);
+                        };
+                        *self.client = client;
+                        ipc(self.client, ipc_command::run(command))?
+                    },
+                };
+                let results: Vec<Value> = from_slice(&reply).unwrap_or_default();
+                let failed: Vec<&str> = results.iter()
+                    .filter(|r| r["success"] == false)
+                    .map(|r| r["error"].as_str().unwrap_or("failed"))
+                    .collect();
+                if failed.is_empty() { Ok(()) } else { Err(Error::Failed(failed.join(", "))) }
+            },
+        }
+    }
+}
+
+/// The workspace argument of sway commands for where a rule sends a window, with the workspace if it
+/// exists already
+fn rule_target<'a>(
+    rule: &WindowRule, output: Option<&str>, workspaces: &'a [Value], tree: &tree::Node, empty: &EmptyConfig,
+) -> Option<(String, Option<&'a Value>)> {
+    let on_output = |w: &&Value| output.is_none_or(|o| w["output"] == o);
+    if rule.first_empty {
+        let mut numbered: Vec<&Value> = workspaces.iter().filter(on_output).filter(|w| num_of(w).is_some()).collect();
+        numbered.sort_by_key(|w| num_of(w));
+        let empty: Option<&Value> = numbered.into_iter().find(|w| {
+            tree::find_workspace(tree, w["name"].as_str().unwrap_or_default()).is_none_or(|t| tree::count_occupying(t, empty) == 0)
+        });
+        return Some(match empty {
+            Some(workspace) => (format!("number {}", num_of(workspace)?), Some(workspace)),
+            None => (format!("number {}", (1..).find(|n| workspaces.iter().all(|w| num_of(w) != Some(*n)))?), None),
+        });
+    }
+    match rule.workspace.as_ref() {
+        Some(WorkspaceSpec::Number(num)) => Some((format!("number {num}"), workspaces.iter().find(|w| num_of(w) == Some(*num)))),
+        Some(WorkspaceSpec::Name(name)) => Some((quote(name), workspaces.iter().find(|w| w["name"] == name.as_str()))),
+        // Only an output, the window goes to the workspace it shows
+        None => {
+            let shown: &Value = workspaces.iter().find(|w| w["visible"] == true && on_output(w) && output.is_some())?;
+            Some((quote(shown["name"].as_str()?), Some(shown)))
+        },
+    }
+}
+
+/// How long the daemon waits for sway to come back after the connection dropped, and how often it tries
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const RECONNECT_POLL: Duration = Duration::from_millis(500);
+
+/// Log what a job of the daemon could not do, the daemon carries on with the next one
+fn report<T, E: fmt::Display>(what: &str, result: Result<T, E>) -> Option<T> {
+    result.map_err(|err| eprintln!("{what}: {err}")).ok()
+}
+
+/// Connect to sway again, at the old socket after a reload or at the socket of a restarted sway
+fn reconnect(old: &Path) -> Option<Client> {
+    let started = Instant::now();
+    while started.elapsed() < RECONNECT_TIMEOUT {
+        thread::sleep(RECONNECT_POLL);
+        let restarted = read_dir(runtime_dir()).into_iter().flatten().flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                name.starts_with("sway-ipc.") && name.ends_with(".sock")
+            });
+        if let Some(client) = std::iter::once(old.to_path_buf()).chain(restarted).find_map(|p| Client::connect_to_path(p).ok()) {
+            return Some(client);
+        }
+    }
+    None
+}
+
+/// How often the daemon looks at the profile schedule
+const PROFILE_POLL: Duration = Duration::from_secs(30);
+
+/// How often idle workspaces are looked for
+fn archive_interval(config: &Config) -> Duration {
+    Duration::from_secs(config.archive.after.clamp(1, 60))
+}
+
+fn next_event(rx: &Receiver<Event>, deadline: Option<Instant>) -> Result<Event, RecvTimeoutError> {
+    match deadline {
+        Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
+/// Workspace names by their last visit in the state file, to start the MRU order from
+fn recently_visited() -> Vec<String> {
+    let mut visits: Vec<(String, state::Visit)> = state::load().visits.into_iter().collect();
+    visits.sort_by_key(|(_, visit)| std::cmp::Reverse(visit.last));
+    visits.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Run the daemon until the connection to sway goes away
+pub fn run(client: &mut Client, config: Config) -> Result<(), Error> {
+    let (tx, rx) = channel();
+    listen(tx.clone())?;
+    let signal_actions = handle_signals(&config, tx.clone());
+    read_fifo(&config, tx.clone());
+    subscribe(client, tx.clone());
+    if let Some(http) = &config.daemon.http {
+        http::serve(http, tx.clone());
+    }
+
+    let mut daemon = Daemon {
+        client,
+        signal_actions,
+        digits: Digits { typed: String::new(), deadline: None },
+        mru: Mru { order: recently_visited(), cycle: Vec::new(), position: 0, deadline: None },
+        lock: LockGate { queued: Vec::new(), next_check: None },
+        burst: Burst { events: Vec::new(), started: None, deadline: None },
+        archiver: Archiver {
+            last_focused: HashMap::new(),
+            next_check: (config.archive.after > 0).then(|| Instant::now() + archive_interval(&config)),
+        },
+        schedule: Schedule {
+            current: profile::scheduled(&config).map(String::from),
+            next_check: config.profiles.values().any(|p| p.hours.is_some()).then(|| Instant::now() + PROFILE_POLL),
+        },
+        wallpapers: Wallpapers::default(),
+        homes: HashMap::new(),
+        config,
+    };
+    if daemon.config.daemon.return_home {
+        report("return home", daemon.return_home(false));
+    }
+    if daemon.config.daemon.session {
+        report("session", daemon.record_session());
+    }
+    if daemon.config.wallpaper.enabled() {
+        for workspace in get_workspaces(daemon.client).unwrap_or_default().iter().filter(|w| w["visible"] == true) {
+            daemon.wallpapers.show(&daemon.config, workspace);
+        }
+    }
+
+    loop {
+        match next_event(&rx, daemon.deadline()) {
+            Ok(Event::Disconnected) => {
+                let Some(client) = reconnect(daemon.client.socket_path()) else {
+                    break;
+                };
+                *daemon.client = client;
+                subscribe(daemon.client, tx.clone());
+                if daemon.config.daemon.session {
+                    report("session", daemon.restore_session());
+                }
+                report("restore shown workspaces", daemon.restore_shown());
+            },
+            Ok(event) => daemon.handle(event),
+            Err(RecvTimeoutError::Timeout) => daemon.tick(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, stdin};
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+
+use ksway::Client;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::daemon::{self, Event, Kind};
+use crate::{get_workspaces, run_words, schema, state};
+
+
+/// Button identifiers read from stdin, one per line
+fn read_buttons(tx: Sender<Event>) {
+    thread::spawn(move || {
+        for line in stdin().lock().lines().map_while(Result::ok) {
+            if tx.send(Event::Command(line.trim().to_string())).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Event::Disconnected);
+    });
+}
+
+/// One line of JSON with the workspaces and what each button does, for the pad to draw its labels
+fn print_state(client: &mut Client, config: &Config) {
+    let workspaces: Vec<Value> = match get_workspaces(client) {
+        Ok(workspaces) => workspaces,
+        Err(err) => {
+            eprintln!("{err}");
+            return;
+        },
+    };
+    let state = state::load();
+    let workspaces: Vec<Value> = workspaces.iter().map(|w| schema::workspace(config, &state, w)).collect();
+    let buttons: BTreeMap<&String, &String> = config.deck.buttons.iter().collect();
+    println!("{}", schema::document(json!({ "workspaces": workspaces, "buttons": buttons })));
+}
+
+/// Run the actions bound to the buttons read from stdin and print the workspace state on every change,
+/// until stdin or the connection to sway closes
+pub fn run(client: &mut Client, config: &Config) {
+    let (tx, rx) = channel();
+    read_buttons(tx.clone());
+    daemon::subscribe(client, tx);
+    print_state(client, config);
+
+    for event in rx {
+        match event {
+            Event::Command(button) if button.is_empty() => {},
+            Event::Command(button) => {
+                let Some(action) = config.deck.buttons.get(&button) else {
+                    eprintln!("no action for button {button}");
+                    continue;
+                };
+                let result = shell_words::split(action).map_err(|e| e.to_string())
+                    .and_then(|words| run_words(client, config, &words).map_err(|e| e.to_string()));
+                if let Err(err) = result {
+                    eprintln!("{action}: {err}");
+                }
+            },
+            Event::Sway(Kind::Workspace, _) => print_state(client, config),
+            Event::Disconnected => break,
+            _ => {},
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::{Config, Numbering};
+
+
+fn roman(mut num: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while num >= value {
+            roman.push_str(numeral);
+            num -= value;
+        }
+    }
+    roman
+}
+
+/// 1 is `a`, 26 is `z`, 27 is `aa`, like spreadsheet columns
+fn letters(mut num: i64) -> String {
+    let mut letters: Vec<char> = Vec::new();
+    while num > 0 {
+        num -= 1;
+        letters.push((b'a' + (num % 26) as u8) as char);
+        num /= 26;
+    }
+    letters.into_iter().rev().collect()
+}
+
+/// How a workspace number is shown, commands keep using the number itself
+pub fn number(config: &Config, num: i64) -> String {
+    if num < 1 {
+        return num.to_string();
+    }
+    if let Some(glyph) = config.display.glyphs.get(num as usize - 1) {
+        return glyph.to_owned();
+    }
+    match config.display.numbers {
+        Numbering::Decimal => num.to_string(),
+        Numbering::Roman if num < 4000 => roman(num),
+        Numbering::Roman => num.to_string(),
+        Numbering::Letters => letters(num),
+    }
+}
+
+/// A workspace name with its number prefix, if any, shown in the configured numbering
+pub fn name(config: &Config, name: &str) -> String {
+    let digits: usize = name.chars().take_while(|c| c.is_ascii_digit()).count();
+    match name[..digits].parse() {
+        Ok(num) => format!("{}{}", number(config, num), &name[digits..]),
+        Err(_) => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{letters, roman};
+
+    #[test]
+    fn roman_numerals() {
+        assert_eq!(roman(1), "I");
+        assert_eq!(roman(4), "IV");
+        assert_eq!(roman(9), "IX");
+        assert_eq!(roman(14), "XIV");
+        assert_eq!(roman(1994), "MCMXCIV");
+        assert_eq!(roman(3999), "MMMCMXCIX");
+    }
+
+    #[test]
+    fn letters_like_spreadsheet_columns() {
+        assert_eq!(letters(1), "a");
+        assert_eq!(letters(26), "z");
+        assert_eq!(letters(27), "aa");
+        assert_eq!(letters(52), "az");
+        assert_eq!(letters(703), "aaa");
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::{Write, stdout};
+use std::sync::mpsc::channel;
+
+use ksway::Client;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::daemon::{self, Event, Kind};
+use crate::{schema, state};
+
+
+/// One line of JSON for a workspace event: the change, the workspace focused before and the one now
+fn describe(config: &Config, event: &Value) -> Value {
+    let state = state::load();
+    let workspace = |key: &str| Some(&event[key]).filter(|w| w.is_object()).map(|w| schema::workspace(config, &state, w));
+    schema::document(json!({
+        "change": event["change"],
+        "old": workspace("old"),
+        "workspace": workspace("current"),
+        "output": event["current"]["output"].as_str(),
+    }))
+}
+
+/// Print every workspace event until killed, the connection to sway closes or nobody reads stdout
+pub fn run(client: &Client, config: &Config) {
+    let (tx, rx) = channel();
+    daemon::subscribe(client, tx);
+
+    for event in rx {
+        let line: Value = match event {
+            Event::Sway(Kind::Workspace, payload) => describe(config, &payload),
+            Event::Disconnected => break,
+            _ => continue,
+        };
+        if writeln!(stdout(), "{line}").is_err() {
+            break;
+        }
+    }
+}
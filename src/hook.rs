@@ -0,0 +1,48 @@
+use std::process::Command;
+use std::thread;
+
+use crate::config::Config;
+
+
+/// Where a switch goes from and to, as the hooks get it
+pub struct Switch<'a> {
+    pub old: &'a str,
+    pub old_output: &'a str,
+    pub new: &'a str,
+    pub new_output: &'a str,
+}
+
+fn command(hook: &str, switch: &Switch) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(hook)
+        .env("SWAY_WORKSPACE_OLD", switch.old)
+        .env("SWAY_WORKSPACE_OLD_OUTPUT", switch.old_output)
+        .env("SWAY_WORKSPACE_NEW", switch.new)
+        .env("SWAY_WORKSPACE_NEW_OUTPUT", switch.new_output);
+    command
+}
+
+/// Run the before hook and wait for it, an error when it fails calls the switch off
+pub fn before(config: &Config, switch: &Switch) -> Result<(), String> {
+    let Some(hook) = &config.hooks.before else {
+        return Ok(());
+    };
+    let status = command(hook, switch).status().map_err(|e| format!("{hook}: {e}"))?;
+    if !status.success() {
+        return Err(format!("before hook failed with {status}, not switching"));
+    }
+    Ok(())
+}
+
+/// Start the after hook, the switch does not wait for it but a long-running daemon reaps it
+pub fn after(config: &Config, switch: &Switch) {
+    let Some(hook) = &config.hooks.after else {
+        return;
+    };
+    match command(hook, switch).spawn() {
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        },
+        Err(err) => eprintln!("{hook}: {err}"),
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::exit;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::HttpConfig;
+use crate::daemon::Event;
+
+
+/// Largest body taken, and how long a client may take to send its request
+const MAX_BODY: usize = 64 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request that made it past the token check
+pub struct Request {
+    pub method: String,
+    /// Path with percent escapes decoded
+    pub path: String,
+    pub body: String,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<String>) -> Response {
+        Response { status, body: body.into() }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        413 => "Content Too Large",
+        502 => "Bad Gateway",
+        _ => "Error",
+    }
+}
+
+fn decode(path: &str) -> String {
+    let bytes: &[u8] = path.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], path.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Read a request and the token it carries, or the response to give when it is malformed or too large
+fn read_request(stream: impl Read) -> Result<(Request, Option<String>), Response> {
+    let malformed = || Response::new(400, r#"{"error":"malformed request"}"#);
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| malformed())?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), decode(path)),
+        _ => return Err(malformed()),
+    };
+
+    let mut length: usize = 0;
+    let mut token: Option<String> = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|_| malformed())?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or_else(malformed)?;
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().map_err(|_| malformed())?,
+            "authorization" => token = value.trim().strip_prefix("Bearer ").map(String::from),
+            _ => {},
+        }
+    }
+    if length > MAX_BODY {
+        return Err(Response::new(413, r#"{"error":"body larger than 64 KiB"}"#));
+    }
+
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).map_err(|_| malformed())?;
+    Ok((Request { method, path, body: String::from_utf8_lossy(&body).to_string() }, token))
+}
+
+/// Compare tokens in time that does not depend on where they differ
+fn same_token(given: Option<&str>, token: &str) -> bool {
+    let Some(given) = given else {
+        return false;
+    };
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn respond(mut stream: TcpStream, response: Response) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, reason(response.status), response.body.len(), response.body,
+    );
+}
+
+/// Serve the HTTP endpoint, requests are answered by the daemon loop since it holds the sway connection
+pub fn serve(config: &HttpConfig, tx: Sender<Event>) {
+    if config.token.is_empty() {
+        eprintln!("[daemon.http] needs a token");
+        exit(1);
+    }
+    let listener = match TcpListener::bind(&config.listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("cannot listen on {}: {err}", config.listen);
+            exit(1);
+        },
+    };
+    let token: String = config.token.to_owned();
+
+    // Every connection gets a thread of its own and a deadline, so a client that sends nothing holds up nobody
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (tx, token) = (tx.clone(), token.to_owned());
+            thread::spawn(move || {
+                let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                let response = match read_request(&stream) {
+                    Err(response) => response,
+                    Ok((_, given)) if !same_token(given.as_deref(), &token) => {
+                        Response::new(401, r#"{"error":"missing or wrong token"}"#)
+                    },
+                    Ok((request, _)) => {
+                        let (reply, answer) = channel();
+                        if tx.send(Event::Http(request, reply)).is_err() {
+                            return;
+                        }
+                        answer.recv().unwrap_or_else(|_| Response::new(500, r#"{"error":"daemon is gone"}"#))
+                    },
+                };
+                respond(stream, response);
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Response, decode, read_request, same_token};
+
+    fn status(result: Result<impl Sized, Response>) -> Option<u16> {
+        result.err().map(|response| response.status)
+    }
+
+    #[test]
+    fn decode_turns_percent_escapes_into_bytes() {
+        assert_eq!(decode("/workspaces/2%3Aweb"), "/workspaces/2:web");
+        assert_eq!(decode("/workspaces/%E4%B8%80"), "/workspaces/一");
+        assert_eq!(decode("/100%"), "/100%");
+        assert_eq!(decode("/%zz"), "/%zz");
+    }
+
+    #[test]
+    fn read_request_takes_the_token_and_the_body() {
+        let raw: &[u8] = b"POST /action HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 4\r\n\r\nnext";
+        let (request, token) = read_request(raw).unwrap_or_else(|_| panic!("request did not parse"));
+        assert_eq!((request.method.as_str(), request.path.as_str(), request.body.as_str()), ("POST", "/action", "next"));
+        assert_eq!(token.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn read_request_refuses_malformed_and_large_requests() {
+        assert_eq!(status(read_request(&b"GET\r\n\r\n"[..])), Some(400));
+        assert_eq!(status(read_request(&b"GET / HTTP/1.1\r\nbroken\r\n\r\n"[..])), Some(400));
+        assert_eq!(status(read_request(&b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"[..])), Some(400));
+        assert_eq!(status(read_request(&b"POST / HTTP/1.1\r\nContent-Length: 70000\r\n\r\n"[..])), Some(413));
+    }
+
+    #[test]
+    fn same_token_wants_the_whole_token() {
+        assert!(same_token(Some("secret"), "secret"));
+        assert!(!same_token(Some("secre"), "secret"));
+        assert!(!same_token(Some("secreT"), "secret"));
+        assert!(!same_token(None, "secret"));
+    }
+}
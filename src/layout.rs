@@ -0,0 +1,299 @@
+use ksway::{Client, ipc_command};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, ipc, quote};
+use crate::tree::{self, Node, contains};
+
+
+/// Window sizes of a workspace relative to the workspace, taken before it moves to another output
+pub struct Proportions {
+    /// Tiling containers with the dimension their parent splits and their share of it
+    tiling: Vec<(i64, &'static str, f64)>,
+    /// Floating windows with position and size as fractions of the workspace
+    floating: Vec<(i64, [f64; 4])>,
+}
+
+fn capture_tiling(node: &Node, tiling: &mut Vec<(i64, &'static str, f64)>) {
+    let dimension: Option<&'static str> = match node.layout.as_str() {
+        "splith" => Some("width"),
+        "splitv" => Some("height"),
+        _ => None,
+    };
+    for child in &node.nodes {
+        if let (Some(dimension), Some(percent)) = (dimension, child.percent) {
+            tiling.push((child.id, dimension, percent));
+        }
+        capture_tiling(child, tiling);
+    }
+}
+
+pub fn capture(workspace: &Node) -> Proportions {
+    let mut tiling = Vec::new();
+    capture_tiling(workspace, &mut tiling);
+
+    let area = &workspace.rect;
+    let floating = workspace.floating_nodes.iter()
+        .filter(|_| area.width > 0 && area.height > 0)
+        .map(|f| {
+            let (width, height) = (area.width as f64, area.height as f64);
+            (f.id, [
+                (f.rect.x - area.x) as f64 / width,
+                (f.rect.y - area.y) as f64 / height,
+                f.rect.width as f64 / width,
+                f.rect.height as f64 / height,
+            ])
+        })
+        .collect();
+
+    Proportions { tiling, floating }
+}
+
+/// Commands that give the moved workspace its old proportions on the new output
+pub fn restore(proportions: &Proportions, workspace: &Node) -> Vec<String> {
+    let present = |id: i64| contains(workspace, id);
+    let area = &workspace.rect;
+
+    let tiling = proportions.tiling.iter()
+        .filter(|(id, _, _)| present(*id))
+        .map(|(id, dimension, percent)| format!("[con_id={id}] resize set {dimension} {} ppt", (percent * 100.0).round()));
+    let floating = proportions.floating.iter()
+        .filter(|(id, _)| present(*id))
+        .map(|(id, [x, y, w, h])| {
+            let (width, height) = (area.width as f64, area.height as f64);
+            format!(
+                "[con_id={id}] resize set {} px {} px; [con_id={id}] move position {} px {} px",
+                (w * width).round(), (h * height).round(), (x * width).round(), (y * height).round(),
+            )
+        });
+    tiling.chain(floating).collect()
+}
+
+/// A container of a layout saved with `layout save`: a window by what identifies it, or a split with
+/// the containers in it
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Saved {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub layout: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<Saved>,
+    /// Floating windows, only the workspace has them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub floating: Vec<Saved>,
+}
+
+impl Saved {
+    fn is_window(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Windows below, in tree order
+    fn windows(&self) -> Vec<&Saved> {
+        if self.is_window() {
+            return vec![self];
+        }
+        self.nodes.iter().flat_map(Saved::windows).collect()
+    }
+
+    fn describe(&self) -> String {
+        let what: Option<&String> = self.app_id.as_ref().or(self.class.as_ref()).or(self.title.as_ref());
+        what.map_or_else(|| "a window".to_string(), |w| w.to_owned())
+    }
+}
+
+fn save_node(node: &Node) -> Saved {
+    let window: bool = node.nodes.is_empty();
+    Saved {
+        layout: if window { String::new() } else { node.layout.to_owned() },
+        percent: node.percent,
+        app_id: node.app_id.to_owned(),
+        class: node.window_properties.as_ref().and_then(|p| p.class.to_owned()),
+        title: node.name.to_owned().filter(|_| window),
+        nodes: node.nodes.iter().map(save_node).collect(),
+        floating: Vec::new(),
+    }
+}
+
+/// The layout of a workspace: how its containers split and which windows they hold
+pub fn save(workspace: &Node) -> Saved {
+    Saved {
+        layout: workspace.layout.to_owned(),
+        nodes: workspace.nodes.iter().map(save_node).collect(),
+        floating: workspace.floating_nodes.iter().map(save_node).collect(),
+        ..Saved::default()
+    }
+}
+
+/// Workspace the windows of the restored workspace wait on while it is rebuilt
+const HOLDING: &str = "sway-workspace-layout";
+/// Mark of the container the next one of a split goes after
+const MARK: &str = "sway-workspace-layout";
+
+/// A window that can take the place of a saved one
+struct Candidate {
+    id: i64,
+    app_id: Option<String>,
+    class: Option<String>,
+    title: Option<String>,
+    /// Whether it was on the workspace being restored
+    home: bool,
+}
+
+/// Rebuilds a saved layout one command at a time, every split needs the id of the container sway
+/// made for the one before
+struct Rebuild<'a> {
+    client: &'a mut Client,
+    workspace: String,
+    candidates: Vec<Candidate>,
+    missing: Vec<String>,
+    /// What went wrong talking to sway, nothing more is sent after it
+    failed: Option<Error>,
+}
+
+impl Rebuild<'_> {
+    fn run(&mut self, command: String) {
+        if self.failed.is_none() {
+            self.failed = ipc(self.client, ipc_command::run(command)).err();
+        }
+    }
+
+    /// Where sway put the containers so far, `None` once talking to sway failed
+    fn tree(&mut self) -> Option<Node> {
+        if self.failed.is_some() {
+            return None;
+        }
+        tree::get_tree(self.client).map_err(|e| self.failed = Some(e)).ok()
+    }
+
+    /// The window standing in for `saved`, one with the same title if there is one
+    fn take(&mut self, saved: &Saved) -> Option<i64> {
+        let fits = |c: &Candidate| c.app_id == saved.app_id && c.class == saved.class;
+        let at: Option<usize> = self.candidates.iter().position(|c| fits(c) && c.title == saved.title)
+            .or_else(|| self.candidates.iter().position(fits));
+        match at {
+            Some(at) => Some(self.candidates.remove(at).id),
+            None => {
+                self.missing.push(saved.describe());
+                None
+            },
+        }
+    }
+
+    /// Put `saved` right after the container `after`, or on the empty workspace without one, and
+    /// return the container it became
+    fn place(&mut self, saved: &Saved, after: Option<i64>) -> Option<i64> {
+        if saved.is_window() {
+            let id: i64 = self.take(saved)?;
+            let to: String = match after {
+                Some(after) => format!("[con_id={after}] mark --add {MARK}; [con_id={id}] move container to mark {MARK}; unmark {MARK}"),
+                None => format!("[con_id={id}] move container to workspace {}", quote(&self.workspace)),
+            };
+            self.run(format!("[con_id={id}] floating disable; {to}"));
+            return Some(id);
+        }
+
+        let mut nodes = saved.nodes.iter();
+        let (first, share) = nodes.by_ref().find_map(|n| Some((self.place(n, after)?, n.percent)))?;
+        // sway does not split the only child of a split, but it does in a tabbed one
+        if after.is_none() {
+            self.run(format!("[con_id={first}] layout tabbed"));
+        }
+        let split: &str = if saved.layout == "splitv" { "v" } else { "h" };
+        self.run(format!("[con_id={first}] split {split}"));
+        let wrapper: i64 = self.tree().and_then(|t| Some(tree::parent_of(&t, first)?.id)).unwrap_or(first);
+
+        let placed: Vec<(i64, Option<f64>)> = self.children((first, share), nodes);
+        self.arrange(first, &saved.layout, &placed);
+        Some(wrapper)
+    }
+
+    /// Place the rest of the children of a split after the first one, returning all of them with
+    /// their share
+    fn children<'s>(&mut self, first: (i64, Option<f64>), rest: impl Iterator<Item = &'s Saved>) -> Vec<(i64, Option<f64>)> {
+        let mut placed: Vec<(i64, Option<f64>)> = vec![first];
+        for node in rest {
+            if let Some(id) = self.place(node, placed.last().map(|(id, _)| *id)) {
+                placed.push((id, node.percent));
+            }
+        }
+        placed
+    }
+
+    /// Give the split holding `first` its layout and its children their sizes
+    fn arrange(&mut self, first: i64, layout: &str, placed: &[(i64, Option<f64>)]) {
+        if !layout.is_empty() {
+            self.run(format!("[con_id={first}] layout {layout}"));
+        }
+        let dimension: &str = match layout {
+            "splith" => "width",
+            "splitv" => "height",
+            _ => return,
+        };
+        let resizes: Vec<String> = placed.iter()
+            .filter_map(|(id, percent)| Some(format!("[con_id={id}] resize set {dimension} {} ppt", ((*percent)? * 100.0).round())))
+            .collect();
+        if placed.len() > 1 && !resizes.is_empty() {
+            self.run(resizes.join("; "));
+        }
+    }
+}
+
+/// Rebuild `saved` on `workspace` from the windows there are, taking them from the workspace first
+/// and then from anywhere outside the scratchpad. Windows the workspace had that the layout does not
+/// use end up after it, the saved windows no window matched are returned
+pub fn rebuild(client: &mut Client, tree: &Node, workspace: &Node, saved: &Saved) -> Result<Vec<String>, Error> {
+    let name: String = workspace.name.to_owned().unwrap_or_default();
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for output in &tree.nodes {
+        for on in output.nodes.iter().filter(|w| w.kind == "workspace" && w.name.as_deref() != Some("__i3_scratch")) {
+            let home: bool = on.id == workspace.id;
+            candidates.extend(tree::windows(on).into_iter().map(|(w, _)| Candidate {
+                id: w.id,
+                app_id: w.app_id.to_owned(),
+                class: w.window_properties.as_ref().and_then(|p| p.class.to_owned()),
+                title: w.name.to_owned(),
+                home,
+            }));
+        }
+    }
+    candidates.sort_by_key(|c| !c.home);
+
+    let mut rebuild = Rebuild { client, workspace: name.to_owned(), candidates, missing: Vec::new(), failed: None };
+    let emptying: Vec<String> = tree::children(workspace)
+        .map(|c| format!("[con_id={}] move container to workspace {}", c.id, quote(HOLDING)))
+        .collect();
+    if !emptying.is_empty() {
+        rebuild.run(emptying.join("; "));
+    }
+
+    let mut nodes = saved.nodes.iter();
+    if let Some((first, share)) = nodes.by_ref().find_map(|n| Some((rebuild.place(n, None)?, n.percent))) {
+        let placed: Vec<(i64, Option<f64>)> = rebuild.children((first, share), nodes);
+        rebuild.arrange(first, &saved.layout, &placed);
+    }
+    for window in saved.floating.iter().flat_map(Saved::windows) {
+        if let Some(id) = rebuild.take(window) {
+            rebuild.run(format!("[con_id={id}] move container to workspace {}; [con_id={id}] floating enable", quote(&name)));
+        }
+    }
+
+    let left: Vec<String> = rebuild.candidates.iter()
+        .filter(|c| c.home)
+        .map(|c| format!("[con_id={}] move container to workspace {}", c.id, quote(&name)))
+        .collect();
+    if !left.is_empty() {
+        rebuild.run(left.join("; "));
+    }
+    match rebuild.failed {
+        Some(err) => Err(err),
+        None => Ok(rebuild.missing),
+    }
+}
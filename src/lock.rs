@@ -0,0 +1,13 @@
+use std::fs::{read_dir, read_to_string};
+
+
+/// Whether one of the given screen locker processes is running
+pub fn is_locked(processes: &[String]) -> bool {
+    let Ok(entries) = read_dir("/proc") else {
+        return false;
+    };
+    entries.flatten()
+        .filter(|e| e.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|e| read_to_string(e.path().join("comm")).ok())
+        .any(|comm| processes.iter().any(|p| p == comm.trim_end()))
+}
@@ -1,37 +1,159 @@
-use std::cmp::Ordering;
-use std::env::var;
+mod archive;
+mod assign;
+mod bar;
+mod cli;
+mod completion;
+mod config;
+mod cursor;
+mod daemon;
+mod deck;
+mod display;
+mod follow;
+mod hook;
+mod http;
+mod layout;
+mod lock;
+mod output;
+mod picker;
+mod profile;
+mod project;
+mod rebalance;
+mod renumber;
+mod schema;
+mod shot;
+mod skip;
+mod state;
+mod template;
+mod tree;
+mod wallpaper;
 
-use clap::{Parser, ValueEnum};
-use ksway::{Client, ipc_command};
-use serde_json::{Value, from_str};
+use std::cmp::Ordering;
+use std::env::{args_os, var};
+use std::fmt;
+use std::io::{IsTerminal, stdin};
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread;
 
+use clap::{CommandFactory, ValueEnum};
+use clap::error::ErrorKind;
+use ksway::{Client, IpcCommand, ipc_command};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, from_slice, from_str, json};
 
-/// Simple command to switch workspaces with optional output awareness for Sway/i3
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-   /// Sway/i3 socket path
-   #[arg(short, long, default_value_t = var("SWAYSOCK").unwrap())]
-   sock: String,
+use config::{Config, Order, WorkspaceSpec};
+use output::{Output, get_outputs};
+use skip::Skip;
 
-   /// Action
-   #[arg(value_enum)]
-   action: Action,
 
-   /// Move to new workspace
-   #[arg(short, long = "move", default_value_t = false)]
-   move_ws: bool,
-
-   /// Do not focus to new workspace
-   #[arg(short, long = "no-focus", default_value_t = false)]
-   no_focus_ws: bool,
+/// Options of an action, each subcommand in `cli` sets the ones it takes and leaves the rest at their defaults
+struct Args {
+    sock: Option<String>,
+    action: Action,
+    /// Positional arguments of the action, like the number of sync-goto or the key of bookmark
+    params: Vec<String>,
+    /// Remember the focused workspace for pop before running the action
+    push: bool,
+    to: Option<i64>,
+    move_ws: bool,
+    merge: bool,
+    no_focus_ws: bool,
+    focus_moved: bool,
+    create: bool,
+    global: bool,
+    layout_aware: bool,
+    /// Work through each output on its own
+    per_output: bool,
+    /// Leave the workspaces outputs show alone
+    hidden: bool,
+    /// Go the other way round
+    reverse: bool,
+    direction: Option<output::Direction>,
+    wrap: Option<bool>,
+    order: Option<Order>,
+    count: u32,
+    under_cursor: bool,
+    on_output: Option<String>,
+    create_on: Option<String>,
+    focus_last: bool,
+    warp_cursor: bool,
+    color: Option<String>,
+    tags: Vec<String>,
+    output: Option<String>,
+    workspace: Option<String>,
+    out: Option<PathBuf>,
+    reveal: bool,
+    blocks: bool,
+    except: Option<String>,
+    yes: bool,
+    /// Run from the command line, where a bulk change may ask first; the daemon, bars and decks never ask
+    interactive: bool,
+    force: bool,
+    stdout_ws: bool,
+    /// Workspaces to pass over on top of `[navigation] skip`
+    skip: Vec<String>,
+    skip_empty: bool,
+    /// Windows goto-window looks for
+    criteria: tree::Criteria,
+    /// Focus the window goto-window found instead of whatever sway picks on its workspace
+    focus_window: bool,
+    format: Format,
+    schema_version: u64,
+    /// Send the action to the daemon instead of running it
+    via_daemon: bool,
+}
 
-   /// Print workspace number to stdout
-   #[arg(short = 'o', long = "stdout", default_value_t = false)]
-   stdout_ws: bool,
+impl Args {
+    fn new(sock: Option<String>, schema_version: u64) -> Args {
+        Args {
+            sock,
+            action: Action::Next,
+            params: Vec::new(),
+            push: false,
+            to: None,
+            move_ws: false,
+            merge: false,
+            no_focus_ws: false,
+            focus_moved: false,
+            create: false,
+            global: false,
+            layout_aware: false,
+            per_output: false,
+            hidden: false,
+            reverse: false,
+            direction: None,
+            wrap: None,
+            order: None,
+            count: 1,
+            under_cursor: false,
+            on_output: None,
+            create_on: None,
+            focus_last: false,
+            warp_cursor: false,
+            color: None,
+            tags: Vec::new(),
+            output: None,
+            workspace: None,
+            out: None,
+            reveal: false,
+            blocks: false,
+            except: None,
+            yes: false,
+            interactive: false,
+            force: false,
+            stdout_ws: false,
+            skip: Vec::new(),
+            skip_empty: false,
+            criteria: tree::Criteria::default(),
+            focus_window: false,
+            format: Format::Text,
+            schema_version,
+            via_daemon: false,
+        }
+    }
 }
 
-#[derive(ValueEnum, Clone)]
+#[derive(Clone)]
 enum Action {
     Next,
     Prev,
@@ -39,26 +161,257 @@ enum Action {
     PrevOutput,
     NextOnOutput,
     PrevOnOutput,
+    SyncGoto,
+    GotoLocal,
+    Digit,
+    Mru,
+    Daemon,
+    MoveAll,
+    Swap,
+    ShiftLeft,
+    ShiftRight,
+    Bookmark,
+    Push,
+    Pop,
+    BackAndForth,
+    Back,
+    Forward,
+    Goto,
+    GotoName,
+    New,
+    Isolate,
+    Insert,
+    NewOnOutput,
+    NextFree,
+    Urgent,
+    GotoWindow,
+    Completions,
+    Set,
+    Cwd,
+    Pick,
+    Unarchive,
+    Label,
+    Group,
+    Rename,
+    Schema,
+    Rebalance,
+    Balance,
+    SwapOutputs,
+    RotateOutputs,
+    MoveToOutput,
+    FocusOutput,
+    Gather,
+    Compact,
+    Query,
+    Deck,
+    Follow,
+    Waybar,
+    I3bar,
+    Polybar,
+    Shot,
+    Windows,
+    KillAll,
+    Profile,
+    Open,
+    Layout,
+}
+
+/// How results are printed
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    /// JSON as described by `schema`
+    Json,
+}
+
+/// Quote a workspace or output name for a sway command
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Workspace to switch to, by number or by its full name
+#[derive(Debug, PartialEq)]
+enum Target {
+    Number(i64),
+    Name(String),
+}
+
+impl fmt::Display for Target {
+    /// The workspace argument of sway commands like `workspace` and `move workspace`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Number(num) => write!(f, "number {num}"),
+            Target::Name(name) => write!(f, "{}", quote(name)),
+        }
+    }
+}
+
+impl Target {
+    /// How sway commands address `workspace`, by number unless it only has a name
+    fn of(workspace: &Value) -> Target {
+        match num_of(workspace) {
+            Some(num) => Target::Number(num),
+            None => Target::Name(workspace["name"].as_str().unwrap_or_default().to_string()),
+        }
+    }
+
+    /// A remembered workspace as it is now, or as it was when it is gone
+    fn remembered(workspace: &state::WorkspaceRef, workspaces: &[Value]) -> Target {
+        match workspace.resolve(workspaces) {
+            Some(found) => Target::of(found),
+            None if workspace.num >= 0 => Target::Number(workspace.num),
+            None => Target::Name(workspace.name.to_owned()),
+        }
+    }
+
+    fn from_spec(spec: &WorkspaceSpec) -> Target {
+        match spec {
+            WorkspaceSpec::Number(num) => Target::Number(*num),
+            WorkspaceSpec::Name(name) => Target::Name(name.to_owned()),
+        }
+    }
+
+    /// Whether `workspace` is the one this target refers to
+    fn matches(&self, workspace: &Value) -> bool {
+        match self {
+            Target::Number(num) => workspace["num"] == *num,
+            Target::Name(name) => workspace["name"] == name.as_str(),
+        }
+    }
+
+    /// What `--stdout` prints
+    fn label(&self) -> String {
+        match self {
+            Target::Number(num) => num.to_string(),
+            Target::Name(name) => name.to_owned(),
+        }
+    }
+}
+
+/// Workspace number, `None` for workspaces that only have a name
+pub fn num_of(workspace: &Value) -> Option<i64> {
+    workspace["num"].as_i64().filter(|n| *n >= 0)
+}
+
+/// Navigation order: numbered workspaces by number, followed by the ones that only have a name
+pub fn order_key(workspace: &Value) -> (bool, i64, String) {
+    let num: i64 = num_of(workspace).unwrap_or(-1);
+    (num < 0, num, workspace["name"].as_str().unwrap_or_default().to_string())
+}
+
+/// Part of a name for natural sorting, runs of digits compare by value and before text
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub enum Chunk {
+    Number(usize, String),
+    Text(String),
+}
+
+/// Sort key ordering names the way people count: `2:code` before `10:chat`
+pub fn natural_key(name: &str) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut rest: &str = name;
+    while let Some(first) = rest.chars().next() {
+        let end: usize = rest.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit()).unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(end);
+        chunks.push(if first.is_ascii_digit() {
+            let digits: &str = run.trim_start_matches('0');
+            Chunk::Number(digits.len(), digits.to_string())
+        } else {
+            Chunk::Text(run.to_string())
+        });
+        rest = tail;
+    }
+    chunks
+}
+
+/// Send one message to sway, a connection that broke fails the action instead of ending the process
+pub fn ipc(client: &mut Client, command: IpcCommand) -> Result<Vec<u8>, Error> {
+    client.ipc(command).map_err(|e| Error::Failed(format!("sway: {e}")))
+}
+
+/// A reply of sway as `T`, a reply that does not parse fails the action like a broken connection
+pub fn reply<T: DeserializeOwned>(reply: &[u8]) -> Result<T, Error> {
+    from_slice(reply).map_err(|e| Error::Failed(format!("sway reply: {e}")))
 }
 
-fn get_workspaces(client: &mut Client) -> Vec<Value> {
-    return from_str(&String::from_utf8_lossy(&client.ipc(ipc_command::get_workspaces()).unwrap())).unwrap();
+pub fn get_workspaces(client: &mut Client) -> Result<Vec<Value>, Error> {
+    reply(&ipc(client, ipc_command::get_workspaces())?)
 }
 
-fn focus_ws(client: &mut Client, num: i64) -> Result<Vec<u8>, ksway::Error> {
-    return client.ipc(ipc_command::run(format!("workspace number {num}")));
+/// What happens once the target workspace is known, every step can be switched on its own
+struct Plan {
+    /// Carry the focused container to the target
+    move_container: bool,
+    /// Containers of the current workspace to move to the target, in their order
+    merge: Vec<i64>,
+    /// Switch to the target workspace
+    focus_workspace: bool,
+    /// Focus the moved container at the target instead of whatever sway picks
+    focus_moved: bool,
+    /// Output to focus before switching, when the action starts from another output than the focused one
+    origin: Option<String>,
+    /// Output to move the target workspace to, because it is created by the switch
+    create_on: Option<String>,
+    /// Window to focus once at the target
+    land_on: Option<i64>,
+    /// Where to put the pointer afterwards, in layout coordinates
+    warp: Option<(i64, i64)>,
 }
 
-fn move_ws(client: &mut Client, num: i64) -> Result<Vec<u8>, ksway::Error> {
-    return client.ipc(ipc_command::run(format!("move workspace number {num}")));
+impl Plan {
+    fn from_args(args: &Args) -> Plan {
+        Plan {
+            move_container: args.move_ws && !matches!(args.action, Action::MoveAll),
+            merge: Vec::new(),
+            focus_workspace: !args.no_focus_ws,
+            focus_moved: args.focus_moved && (args.move_ws || args.merge || matches!(args.action, Action::MoveAll)),
+            origin: None,
+            create_on: None,
+            land_on: None,
+            warp: None,
+        }
+    }
+
+    /// Issue all steps as one command, `con` is the container focused before the action
+    fn run(&self, client: &mut Client, target: &Target, con: Option<i64>) -> Result<Vec<u8>, Error> {
+        let criteria: String = con.map(|id| format!("[con_id={id}] ")).unwrap_or_default();
+        let mut commands: Vec<String> = Vec::new();
+
+        if self.move_container {
+            commands.push(format!("{criteria}move workspace {target}"));
+        }
+        commands.extend(self.merge.iter().map(|id| format!("[con_id={id}] move container to workspace {target}")));
+        if self.focus_workspace {
+            if let Some(output) = &self.origin {
+                commands.push(format!("focus output {}", quote(output)));
+            }
+            commands.push(format!("workspace {target}"));
+            if let Some(output) = &self.create_on {
+                commands.push(format!("move workspace to output {}", quote(output)));
+            }
+        }
+        if self.focus_moved && con.is_some() {
+            commands.push(format!("{criteria}focus"));
+        } else if let Some(id) = self.land_on.filter(|_| self.focus_workspace) {
+            commands.push(format!("[con_id={id}] focus"));
+        }
+        if let Some((x, y)) = self.warp {
+            commands.push(format!("seat - cursor set {x} {y}"));
+        }
+
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        ipc(client, ipc_command::run(commands.join("; ")))
+    }
 }
 
-fn find_by(workspaces: &Vec<Value>, current: i64, step: i64) -> i64 {
-    let existing: Vec<i64> = workspaces.into_iter().map(|w| w["num"].as_i64().unwrap()).collect();
+fn find_by(workspaces: &[Value], current: i64, step: i64) -> i64 {
+    let existing: Vec<i64> = workspaces.iter().filter_map(num_of).collect();
 
     let mut next: i64 = current + step;
     let first: i64 = 1;
-    let last: i64 = existing.into_iter().max().unwrap();
+    let last: i64 = existing.into_iter().max().unwrap_or(current);
 
     if current == last && step > 0 {
         next = last + step;
@@ -68,16 +421,45 @@ fn find_by(workspaces: &Vec<Value>, current: i64, step: i64) -> i64 {
         next = last;
     }
 
-    return next;
+    next
+}
+
+/// Most numbers `step_over` passes over before it gives up
+const MAX_SKIPPED: usize = 1000;
+
+/// Take `step` from `num` and on past the numbers to skip, staying at `num` when nothing is left past them
+fn step_over(skipped: impl Fn(i64) -> bool, num: i64, step: impl Fn(i64) -> i64) -> i64 {
+    let mut next: i64 = step(num);
+    for _ in 0..MAX_SKIPPED {
+        if !skipped(next) {
+            return next;
+        }
+        let after: i64 = step(next);
+        if after == next {
+            break;
+        }
+        next = after;
+    }
+    num
+}
+
+/// The output whose number range in the config holds `num`
+fn range_owner<'a>(config: &'a Config, outputs: &'a [Output], num: i64) -> Option<&'a str> {
+    outputs.iter()
+        .find(|o| output::lookup(&config.ranges, outputs, &o.name).is_some_and(|r| r.contains(num)))
+        .map(|o| o.name.as_str())
 }
 
-fn find_on_output(workspaces: &Vec<Value>, current: i64, step: i64, output: String) -> i64 {
-    let other_wss: Vec<&Value> = workspaces.into_iter().filter(|w| w["output"].to_string() != output).collect();
-    let other_nums: Vec<i64> = other_wss.into_iter().map(|w| w["num"].as_i64().unwrap()).collect();
+/// `reserved` holds numbers sway assigns to other outputs, they bound the range like existing workspaces do
+pub fn find_on_output(workspaces: &[Value], current: i64, step: i64, output: &str, reserved: &[i64]) -> i64 {
+    let other_wss: Vec<&Value> = workspaces.iter().filter(|w| w["output"] != output).collect();
+    let other_nums: Vec<i64> = other_wss.into_iter().filter_map(num_of)
+        .chain(reserved.iter().copied())
+        .collect();
 
     let other_nums_prev: Vec<i64> = [
         Vec::from([0]),
-        other_nums.to_owned().into_iter().filter(|n| n < &current).collect()
+        other_nums.iter().copied().filter(|n| n < &current).collect()
     ].concat();
     let other_nums_next: Vec<i64> = other_nums.into_iter().filter(|n| n > &current).collect();
 
@@ -85,7 +467,7 @@ fn find_on_output(workspaces: &Vec<Value>, current: i64, step: i64, output: Stri
 
     let first: i64 = other_nums_prev.into_iter().max().unwrap() + 1;
 
-    let last: i64 = if other_nums_next.len() == 0 {
+    let last: i64 = if other_nums_next.is_empty() {
         next
     } else {
         other_nums_next.into_iter().min().unwrap() - 1
@@ -97,55 +479,1429 @@ fn find_on_output(workspaces: &Vec<Value>, current: i64, step: i64, output: Stri
         next = last;
     }
 
-    return next;
+    next
+}
+
+/// One step from `current` through the workspaces of `scope`, the ones with only a name come after the
+/// numbered ones; `numbered` takes the steps between numbers and the one past the last named workspace
+fn step_through(scope: &[&Value], current: &Value, step: i64, numbered: impl Fn(i64) -> i64) -> Target {
+    let mut named: Vec<&str> = scope.iter().filter(|w| num_of(w).is_none()).filter_map(|w| w["name"].as_str()).collect();
+    named.sort();
+    let last: Option<i64> = scope.iter().filter_map(|w| num_of(w)).max();
+    let name: &str = current["name"].as_str().unwrap_or_default();
+    let position: Option<usize> = named.iter().position(|n| *n == name);
+
+    match (num_of(current), step.cmp(&0), position) {
+        (Some(num), Ordering::Greater, _) if last.is_none_or(|l| num >= l) && !named.is_empty() => {
+            Target::Name(named[0].to_string())
+        },
+        (Some(num), _, _) => Target::Number(numbered(num)),
+        (None, Ordering::Greater, Some(i)) if i + 1 < named.len() => Target::Name(named[i + 1].to_string()),
+        (None, Ordering::Greater, _) => Target::Number(numbered(last.unwrap_or(0))),
+        (None, Ordering::Less, Some(i)) if i > 0 => Target::Name(named[i - 1].to_string()),
+        (None, Ordering::Less, _) if last.is_some() => Target::Number(last.unwrap_or_default()),
+        (None, _, _) => Target::of(current),
+    }
 }
 
-fn find_output(workspaces: &Vec<Value>, current: i64, step: i64, output: String) -> i64 {
-    let other_wss: Vec<&Value> = workspaces.into_iter().filter(|w| w["output"].to_string() != output && w["visible"] == true).collect();
+/// One step from `current` through the workspaces of `scope` sorted by name, stopping at either end
+/// unless `wrap` goes round
+fn step_by_name(scope: &[&Value], current: &Value, step: i64, wrap: bool) -> Target {
+    let name = |w: &Value| w["name"].as_str().unwrap_or_default().to_string();
+    let mut names: Vec<String> = scope.iter().map(|w| name(w)).collect();
+    names.sort_by_key(|n| natural_key(n));
+    let Some(position) = names.iter().position(|n| *n == name(current)) else {
+        return Target::of(current);
+    };
+    let last: i64 = names.len() as i64 - 1;
+    let next: i64 = match position as i64 + step.signum() {
+        n if wrap => n.rem_euclid(last + 1),
+        n => n.clamp(0, last),
+    };
+    scope.iter().find(|w| name(w) == names[next as usize]).map_or_else(|| Target::of(current), |w| Target::of(w))
+}
 
-    let other_prevs: Vec<&Value> = other_wss.to_owned().into_iter().filter(|w| w["num"].as_i64().unwrap() < current).collect();
-    let other_nexts: Vec<&Value> = other_wss.into_iter().filter(|w| w["num"].as_i64().unwrap() > current).collect();
+/// Take `count` steps from `current`, every step starting where the one before landed as if the action
+/// ran that many times. A step to a workspace that does not exist lands on a new one on `output`
+fn repeat(workspaces: &[Value], current: &Value, output: &str, count: u32, step: &dyn Fn(&[Value], &Value) -> Target) -> Target {
+    let mut workspaces: Vec<Value> = workspaces.to_owned();
+    let mut current: Value = current.to_owned();
+    let mut target: Target = Target::of(&current);
+    for _ in 0..count {
+        target = step(&workspaces, &current);
+        current = match workspaces.iter().find(|w| target.matches(w)) {
+            Some(landed) => landed.to_owned(),
+            None => {
+                let num: i64 = if let Target::Number(num) = target { num } else { -1 };
+                let created = json!({ "num": num, "name": target.label(), "output": output });
+                workspaces.push(created.to_owned());
+                created
+            },
+        };
+    }
+    target
+}
 
+/// The workspace at the other end when stepping from `current` leaves the workspaces of `scope`
+fn wrapped(scope: &[&Value], current: &Value, step: i64) -> Option<Target> {
+    let first: &Value = scope.iter().min_by_key(|w| order_key(w))?;
+    let last: &Value = scope.iter().max_by_key(|w| order_key(w))?;
     match step.cmp(&0) {
-        Ordering::Less => {
-            return if other_prevs.len() == 0 { current } else { other_prevs.last().unwrap()["num"].as_i64().unwrap() }
+        Ordering::Greater if order_key(current) >= order_key(last) => Some(Target::of(first)),
+        Ordering::Less if order_key(current) <= order_key(first) => Some(Target::of(last)),
+        _ => None,
+    }
+}
+
+/// The workspace the output `step` outputs away from `output` shows, staying put past either end
+/// without `wrap`
+fn find_output(outputs: &[Output], workspaces: &[Value], current: &Value, step: i64, output: &str, wrap: bool) -> Target {
+    let found: Option<&Output> = outputs.iter().position(|o| o.name == output).and_then(|at| {
+        let next: i64 = at as i64 + step;
+        let next: i64 = if wrap { next.rem_euclid(outputs.len() as i64) } else { next };
+        outputs.get(usize::try_from(next).ok()?)
+    });
+    let shown: Option<&Value> = found.and_then(|o| workspaces.iter().find(|w| w["visible"] == true && w["output"] == o.name.as_str()));
+    Target::of(shown.unwrap_or(current))
+}
+
+/// Switch every other output to `num` plus its offset, returns the target for the focused output
+fn sync_goto(client: &mut Client, config: &Config, outputs: &[Output], num: i64) -> Result<i64, Error> {
+    let focused: usize = outputs.iter().position(|o| o.focused).unwrap_or(0);
+
+    let mut commands: Vec<String> = outputs.iter().enumerate()
+        .filter(|(i, _)| *i != focused)
+        .map(|(i, o)| format!("focus output {}; workspace number {}", quote(&o.name), num + config.offset(outputs, i)))
+        .collect();
+
+    match outputs.get(focused) {
+        Some(output) => {
+            if !commands.is_empty() {
+                commands.push(format!("focus output {}", quote(&output.name)));
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            Ok(num + config.offset(outputs, focused))
+        },
+        None => Ok(num),
+    }
+}
+
+/// Step the workspace index of every output at once, the index is the number minus the output's offset
+fn global_step(client: &mut Client, config: &Config, outputs: &[Output], current: i64, step: i64) -> Result<i64, Error> {
+    let offset: i64 = outputs.iter().enumerate()
+        .find(|(_, o)| o.focused)
+        .map(|(i, _)| config.offset(outputs, i))
+        .unwrap_or(0);
+    sync_goto(client, config, outputs, (current - offset + step).max(1))
+}
+
+/// Show the workspaces of a set on their outputs, returns the target for the output that keeps focus
+fn activate_set(
+    client: &mut Client, config: &Config, outputs: &[Output], workspaces: &[Value], tree: Option<&tree::Node>,
+    name: &str, create: bool,
+) -> Result<Option<Target>, Error> {
+    let Some(set) = config.sets.get(name) else {
+        return Err(Error::Failed(format!("no workspace set {name}")));
+    };
+
+    let mut entries: Vec<(&Output, Target)> = outputs.iter()
+        .filter_map(|o| output::lookup(set, outputs, &o.name).map(|spec| (o, Target::from_spec(spec))))
+        .filter(|(_, t)| create || workspaces.iter().any(|w| t.matches(w)))
+        .collect();
+
+    // The focused output goes last so that it keeps the focus
+    let Some(last) = entries.iter().position(|(o, _)| o.focused).or(entries.len().checked_sub(1)) else {
+        return Ok(None);
+    };
+    let (output, target) = entries.remove(last);
+
+    let mut commands: Vec<String> = entries.iter()
+        .map(|(o, t)| format!("focus output {0}; workspace {t}; move workspace to output {0}", quote(&o.name)))
+        .collect();
+    commands.push(format!("focus output {0}; workspace {target}; move workspace to output {0}", quote(&output.name)));
+
+    // Workspaces coming from another output keep their proportions instead of their pixel sizes
+    let moved: Vec<(String, layout::Proportions)> = entries.iter().map(|(o, t)| (*o, t)).chain([(output, &target)])
+        .filter_map(|(o, t)| workspaces.iter().find(|w| t.matches(w) && w["output"] != o.name.as_str()))
+        .filter_map(|w| w["name"].as_str())
+        .filter_map(|name| Some((name.to_string(), layout::capture(tree::find_workspace(tree?, name)?))))
+        .collect();
+
+    ipc(client, ipc_command::run(commands.join("; ")))?;
+
+    if !moved.is_empty() {
+        let after: tree::Node = tree::get_tree(client)?;
+        let restore: Vec<String> = moved.iter()
+            .filter_map(|(name, proportions)| Some(layout::restore(proportions, tree::find_workspace(&after, name)?)))
+            .flatten()
+            .collect();
+        if !restore.is_empty() {
+            ipc(client, ipc_command::run(restore.join("; ")))?;
+        }
+    }
+
+    Ok(Some(target))
+}
+
+/// Move every container of the workspace to `num` in a single command, keeping their order
+fn move_all(client: &mut Client, tree: &tree::Node, current: &str, num: i64) -> Result<i64, Error> {
+    let Some(workspace) = tree::find_workspace(tree, current) else {
+        return Ok(num);
+    };
+
+    let commands: Vec<String> = tree::children(workspace)
+        .map(|c| format!("[con_id={}] move container to workspace number {num}", c.id))
+        .collect();
+
+    if !commands.is_empty() {
+        ipc(client, ipc_command::run(commands.join("; ")))?;
+    }
+
+    Ok(num)
+}
+
+/// Queries an action needs besides the workspaces
+struct Needs {
+    outputs: bool,
+    tree: bool,
+    assignments: bool,
+}
+
+/// What sway reports before an action runs
+struct Snapshot {
+    workspaces: Vec<Value>,
+    /// Only queried when asked for, empty otherwise
+    outputs: Vec<Output>,
+    tree: Option<tree::Node>,
+    assignments: Vec<assign::Assignment>,
+}
+
+impl Snapshot {
+    /// Query the workspaces and whatever else is needed, the extra queries run in parallel
+    /// over their own connections and fall back to the main one when those cannot be opened or fail
+    fn take(client: &mut Client, config: &Config, needs: Needs) -> Result<Snapshot, Error> {
+        let path: PathBuf = client.socket_path().to_path_buf();
+        let connect = || Client::connect_to_path(path.to_owned()).ok();
+
+        let (workspaces, fetched_outputs, fetched_tree, fetched_assignments) = thread::scope(|s| {
+            let outputs = needs.outputs.then(|| s.spawn(|| connect().and_then(|mut c| get_outputs(&mut c, &config.outputs).ok())));
+            let tree = needs.tree.then(|| s.spawn(|| connect().and_then(|mut c| tree::get_tree(&mut c).ok())));
+            let assignments = needs.assignments.then(|| s.spawn(|| connect().and_then(|mut c| assign::get_assignments(&mut c).ok())));
+            let workspaces: Result<Vec<Value>, Error> = get_workspaces(client);
+            (
+                workspaces,
+                outputs.map(|h| h.join().ok().flatten()),
+                tree.map(|h| h.join().ok().flatten()),
+                assignments.map(|h| h.join().ok().flatten()),
+            )
+        });
+
+        Ok(Snapshot {
+            workspaces: workspaces?,
+            outputs: match fetched_outputs {
+                Some(outputs) => outputs.map_or_else(|| get_outputs(client, &config.outputs), Ok)?,
+                None => Vec::new(),
+            },
+            tree: fetched_tree.map(|tree| tree.map_or_else(|| tree::get_tree(client), Ok)).transpose()?,
+            assignments: match fetched_assignments {
+                Some(assignments) => assignments.map_or_else(|| assign::get_assignments(client), Ok)?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+/// Why an action could not be carried out
+pub enum Error {
+    /// Wrong or missing arguments, reported the way clap reports them
+    Usage(clap::Error),
+    /// Nothing to do, like an unknown bookmark or an empty stack
+    Failed(String),
+}
+
+impl Error {
+    fn usage(kind: ErrorKind, message: impl fmt::Display) -> Error {
+        Error::Usage(cli::Cli::command().error(kind, message))
+    }
+
+    /// Report the error and exit with clap's exit code for usage errors, 1 otherwise
+    fn exit(self) -> ! {
+        match self {
+            Error::Usage(err) => err.exit(),
+            Error::Failed(message) => {
+                eprintln!("{message}");
+                exit(1);
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(err) => write!(f, "{}", err.to_string().lines().next().unwrap_or_default()),
+            Error::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+fn require_param<'a>(args: &'a Args, index: usize, error: &str) -> Result<&'a str, Error> {
+    match args.params.get(index) {
+        Some(param) => Ok(param),
+        None => Err(Error::usage(ErrorKind::MissingRequiredArgument, error)),
+    }
+}
+
+fn require_number(args: &Args, error: &str) -> Result<i64, Error> {
+    require_param(args, 0, error)?.parse().map_err(|_| Error::usage(ErrorKind::InvalidValue, error))
+}
+
+/// Show the planned commands of a bulk change and ask before running them, `--yes` skips the question
+fn confirm(args: &Args, commands: &[String]) -> Result<(), Error> {
+    if args.yes || commands.len() < 2 {
+        return Ok(());
+    }
+    if !args.interactive || !stdin().is_terminal() {
+        return Err(Error::Failed(format!("refusing to run {} commands without --yes", commands.len())));
+    }
+
+    for command in commands {
+        eprintln!("{command}");
+    }
+    eprint!("Run these {} commands? [y/N] ", commands.len());
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).map_err(|e| Error::Failed(e.to_string()))?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => Err(Error::Failed("aborted".to_string())),
+    }
+}
+
+/// Mark `kill-all --except` puts on the windows it spares while it runs
+const EXCEPT_MARK: &str = "_sway_workspace_except";
+
+/// `kill-all` closes every window on a workspace but those matching `--except`
+fn kill_all(args: &Args, client: &mut Client, tree: Option<tree::Node>, workspace: &Value) -> Result<(), Error> {
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+
+    // Sway matches the criteria itself, the windows it marks are spared
+    let tree: tree::Node = match &args.except {
+        Some(criteria) => {
+            let reply: Value = from_slice(&ipc(client, ipc_command::run(format!("{criteria} mark --add {EXCEPT_MARK}")))?)
+                .unwrap_or_default();
+            if reply[0]["parse_error"] == true {
+                return Err(Error::Failed(format!("invalid criteria {criteria}: {}", reply[0]["error"].as_str().unwrap_or_default())));
+            }
+            tree::get_tree(client)?
+        },
+        None => tree.unwrap(),
+    };
+    let commands: Vec<String> = tree::find_workspace(&tree, name).map(tree::windows).unwrap_or_default().iter()
+        .filter(|(window, _)| !window.marks.iter().any(|m| m == EXCEPT_MARK))
+        .map(|(window, _)| format!("[con_id={}] kill", window.id))
+        .collect();
+
+    let confirmed = confirm(args, &commands);
+    if args.except.is_some() {
+        ipc(client, ipc_command::run(format!("[con_mark=\"{EXCEPT_MARK}\"] unmark {EXCEPT_MARK}")))?;
+    }
+    confirmed?;
+    if !commands.is_empty() {
+        ipc(client, ipc_command::run(commands.join("; ")))?;
+    }
+    Ok(())
+}
+
+/// Workspace by name or number
+fn workspace_by<'a>(workspaces: &'a [Value], wanted: &str) -> Result<&'a Value, Error> {
+    workspaces.iter()
+        .find(|w| w["name"] == wanted || wanted.parse::<i64>().is_ok_and(|n| w["num"] == n))
+        .ok_or_else(|| Error::Failed(format!("no workspace {wanted}")))
+}
+
+/// Workspace named by `--workspace`, by name or number, `current` without it
+fn chosen_workspace<'a>(args: &Args, workspaces: &'a [Value], current: &'a Value) -> Result<&'a Value, Error> {
+    match &args.workspace {
+        Some(wanted) => workspace_by(workspaces, wanted),
+        None => Ok(current),
+    }
+}
+
+fn numbered(workspace: &Value) -> Result<i64, Error> {
+    num_of(workspace).ok_or_else(|| Error::Failed(format!("workspace {} has no number", workspace["name"].as_str().unwrap_or_default())))
+}
+
+/// The closest numbered workspace before `num` when `step` is negative, or else after it
+fn neighbour(workspaces: &[Value], num: i64, step: i64) -> Option<&Value> {
+    workspaces.iter()
+        .filter(|w| num_of(w).is_some_and(|n| (n - num).signum() == step.signum()))
+        .min_by_key(|w| num_of(w).map(|n| (n - num).abs()))
+}
+
+/// Give `workspace` the number `num` and the workspace that has it now the number of `workspace`,
+/// both keeping the rest of their names
+fn swap(client: &mut Client, workspaces: &[Value], workspace: &Value, num: i64) -> Result<(), Error> {
+    let name = |w: &Value| w["name"].as_str().unwrap_or_default().to_string();
+    let mut renames: Vec<renumber::Rename> = vec![(name(workspace), renumber::with_number(&name(workspace), num))];
+    if let Some(other) = workspaces.iter().find(|w| num_of(w) == Some(num) && w["id"] != workspace["id"]) {
+        renames.push((name(other), renumber::with_number(&name(other), numbered(workspace)?)));
+    }
+    let commands: Vec<String> = renumber::commands(&renames);
+    if !commands.is_empty() {
+        ipc(client, ipc_command::run(commands.join("; ")))?;
+    }
+    Ok(())
+}
+
+/// `label set <WS>` stores the color and tags of a workspace, `label clear <WS>` drops them
+fn label(args: &Args, workspaces: &[Value]) -> Result<(), Error> {
+    let usage = "label requires set|clear <WS>";
+    let workspace: &str = require_param(args, 1, usage)?;
+    // Labels belong to the full name, a number stands for the workspace that has it
+    let name: String = workspaces.iter()
+        .find(|w| w["name"] == workspace || workspace.parse::<i64>().is_ok_and(|n| w["num"] == n))
+        .and_then(|w| w["name"].as_str())
+        .unwrap_or(workspace)
+        .to_string();
+
+    let set: bool = match require_param(args, 0, usage)? {
+        "set" => true,
+        "clear" => false,
+        _ => return Err(Error::usage(ErrorKind::InvalidValue, usage)),
+    };
+    state::update(|state| {
+        if !set {
+            state.labels.remove(&name);
+            return;
+        }
+        let label = state.labels.entry(name).or_default();
+        if args.color.is_some() {
+            label.color = args.color.to_owned();
+        }
+        if !args.tags.is_empty() {
+            label.tags = args.tags.to_owned();
+        }
+    }).map_err(Error::Failed)
+}
+
+/// `group switch <NAME>` makes a group the active one and lands on its first workspace when the
+/// current one is in another group, `group move <NAME>` puts the current workspace into a group and
+/// `group clear` lets navigation go through every group again
+fn group(args: &Args, current: &Value, workspaces: &[Value]) -> Result<Option<Target>, Error> {
+    let usage = "group requires switch|move <NAME> or clear";
+    let current_name: &str = current["name"].as_str().unwrap_or_default();
+    let op: &str = require_param(args, 0, usage)?;
+    let name: &str = match op {
+        "switch" | "move" => require_param(args, 1, usage)?,
+        "clear" => "",
+        _ => return Err(Error::usage(ErrorKind::InvalidValue, usage)),
+    };
+    state::update(|state| match op {
+        "switch" => {
+            state.group = Some(name.to_string());
+            let mut members: Vec<&Value> = workspaces.iter()
+                .filter(|w| w["name"].as_str().and_then(|n| state.groups.get(n)).is_some_and(|g| g == name))
+                .collect();
+            members.sort_by_key(|w| order_key(w));
+            members.first().filter(|_| state.in_other_group(current_name)).map(|w| Target::of(w))
+        },
+        "move" => {
+            state.groups.insert(current_name.to_string(), name.to_string());
+            None
+        },
+        _ => {
+            state.group = None;
+            None
+        },
+    }).map_err(Error::Failed)
+}
+
+/// `layout save <NAME>` writes the layout of a workspace to a file, `layout restore <NAME>` rebuilds it
+fn layout(args: &Args, client: &mut Client, tree: &tree::Node, workspace: &Value) -> Result<(), Error> {
+    let usage = "layout requires save|restore <NAME>";
+    let name: &str = require_param(args, 1, usage)?;
+    if name.is_empty() || name.contains('/') {
+        return Err(Error::usage(ErrorKind::InvalidValue, "layout names cannot be empty or contain a slash"));
+    }
+    let path: PathBuf = state::layout_path(name);
+    let ws_name: &str = workspace["name"].as_str().unwrap_or_default();
+    let node: &tree::Node = tree::find_workspace(tree, ws_name).ok_or_else(|| Error::Failed(format!("no workspace {ws_name}")))?;
+
+    match require_param(args, 0, usage)? {
+        "save" => {
+            let saved: String = serde_json::to_string_pretty(&layout::save(node)).unwrap();
+            path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&path, saved))
+                .map_err(|e| Error::Failed(format!("{}: {e}", path.display())))
         },
-        Ordering::Greater => {
-            return if other_nexts.len() == 0 { current } else { other_nexts.first().unwrap()["num"].as_i64().unwrap() }
+        "restore" => {
+            let saved: layout::Saved = std::fs::read_to_string(&path).ok()
+                .and_then(|content| from_str(&content).ok())
+                .ok_or_else(|| Error::Failed(format!("no layout {name}")))?;
+            for missing in layout::rebuild(client, tree, node, &saved)? {
+                eprintln!("layout {name}: no window for {missing}");
+            }
+            Ok(())
         },
-        Ordering::Equal => return current,
+        _ => Err(Error::usage(ErrorKind::InvalidValue, usage)),
     }
 }
 
-fn main() {
-    let args: Args = Args::parse();
+/// `bookmark set <KEY>` remembers the current workspace, `bookmark go <KEY>` returns the target
+fn bookmark(args: &Args, workspaces: &[Value], current: &Value) -> Result<Option<Target>, Error> {
+    let usage = "bookmark requires set|go <KEY>";
+    let key: &str = require_param(args, 1, usage)?;
+    if key.chars().count() != 1 {
+        return Err(Error::usage(ErrorKind::InvalidValue, "bookmark keys are single characters"));
+    }
 
-    let mut client = Client::connect_to_path(args.sock.to_owned()).unwrap();
+    match require_param(args, 0, usage)? {
+        "set" => {
+            state::update(|state| state.bookmarks.insert(key.to_string(), state::WorkspaceRef::from_value(current))).map_err(Error::Failed)?;
+            Ok(None)
+        },
+        "go" => match state::load().bookmarks.get(key) {
+            Some(mark) => Ok(Some(Target::remembered(mark, workspaces))),
+            None => Err(Error::Failed(format!("no bookmark {key}"))),
+        },
+        _ => Err(Error::usage(ErrorKind::InvalidValue, usage)),
+    }
+}
+
+fn check_schema_version(args: &Args) -> Result<(), Error> {
+    if args.schema_version != schema::VERSION {
+        return Err(Error::usage(ErrorKind::InvalidValue, format!("this build speaks schema version {}", schema::VERSION)));
+    }
+    Ok(())
+}
+
+fn parse_words(words: &[String]) -> Result<Args, Error> {
+    let args: Args = cli::parse(words).map_err(Error::Usage)?;
+    check_schema_version(&args)?;
+    match args.action {
+        Action::Daemon | Action::Digit | Action::Mru | Action::Completions | Action::Schema | Action::Deck | Action::Follow | Action::Waybar | Action::I3bar | Action::Polybar => {
+            Err(Error::Failed(format!("{} cannot be run by the daemon", words.join(" "))))
+        },
+        _ => Ok(args),
+    }
+}
+
+/// Check that command line words, without the program name, form an action the daemon can run
+pub fn check_words(words: &[String]) -> Result<(), Error> {
+    parse_words(words).map(|_| ())
+}
+
+/// Run an action given as command line words, without the program name, for the daemon, a bar or a
+/// deck: `config` is the one they read at start, with the active profile laid over it for each action
+pub fn run_words(client: &mut Client, config: &Config, words: &[String]) -> Result<(), Error> {
+    let mut config: Config = config.clone();
+    profile::apply(&mut config, &state::load());
+    run(parse_words(words)?, client, config)
+}
+
+/// Compute the target of a workspace action and carry out the plan
+fn run(args: Args, client: &mut Client, mut config: Config) -> Result<(), Error> {
+
+    // Everything the action needs is queried up front and at once
+    let action: &Action = &args.action;
+    let needs = Needs {
+        outputs: args.under_cursor || args.on_output.is_some() || args.warp_cursor || args.create_on.is_some() || !config.create_on.is_empty() || !config.ranges.is_empty() || config.numbering.decades
+            || matches!(action, Action::SyncGoto | Action::GotoLocal | Action::Set | Action::Profile | Action::NextOutput | Action::PrevOutput | Action::Rebalance | Action::Balance | Action::SwapOutputs | Action::RotateOutputs | Action::MoveToOutput | Action::FocusOutput)
+            || ((args.per_output || config.compact.per_output) && matches!(action, Action::Compact))
+            || ((args.global || args.layout_aware || config.navigation.layout_aware) && matches!(action, Action::Next | Action::Prev))
+            || (args.global && matches!(action, Action::Goto)),
+        tree: args.focus_last || args.skip_empty || args.merge || (config.guard.fullscreen && !args.force) || matches!(action, Action::MoveAll | Action::Set | Action::Profile | Action::Windows | Action::KillAll | Action::GotoWindow | Action::Layout) || (args.focus_moved && args.move_ws),
+        assignments: args.warp_cursor || matches!(action, Action::NextOnOutput | Action::PrevOnOutput | Action::NewOnOutput),
+    };
+    let Snapshot { workspaces: all_workspaces, outputs, tree, assignments } = Snapshot::take(client, &config, needs)?;
+    config.ranges = config.output_ranges(&outputs);
+    // Between a workspace going away and the next one getting the focus sway reports none focused
+    let focused_ws: &Value = all_workspaces.iter().find(|w| w["focused"] == true)
+        .ok_or_else(|| Error::Failed("no workspace is focused".to_string()))?;
+
+    // The pointer's output or the one given stands in for the focused one, its visible workspace is the origin
+    let pointed: Option<&Output> = if args.under_cursor {
+        let (x, y) = cursor::position(&config).map_err(Error::Failed)?;
+        cursor::output_at(&outputs, x, y)
+    } else if let Some(reference) = &args.on_output {
+        output::resolve(&outputs, reference).ok_or_else(|| Error::Failed(format!("no output {reference}")))?.into()
+    } else {
+        None
+    };
+    let pointed: Option<&Output> = pointed.filter(|o| !o.focused);
+    let current_ws: &Value = pointed
+        .and_then(|o| all_workspaces.iter().find(|w| w["visible"] == true && w["output"] == o.name.as_str()))
+        .unwrap_or(focused_ws);
+
+    // Archived and skipped workspaces and the ones of other groups than the active one are left out
+    // of navigation unless they are the current one
+    let groups: state::State = state::load();
+    let grouped_out = |workspace: &Value| groups.in_other_group(workspace["name"].as_str().unwrap_or_default());
+    let flagged: Vec<WorkspaceSpec> = args.skip.iter().map(|s| WorkspaceSpec::Name(s.to_owned())).collect();
+    let skip: Skip = Skip::new(config.navigation.skip.iter().chain(&flagged)).map_err(Error::Failed)?;
+    // With --skip-empty only workspaces with windows count, numbers without a workspace are empty too
+    let empty = |workspace: &Value| args.skip_empty && tree.as_ref().is_some_and(|t| {
+        tree::find_workspace(t, workspace["name"].as_str().unwrap_or_default()).is_none_or(|w| tree::count_occupying(w, &config.empty) == 0)
+    });
+    let skipped = |num: i64| {
+        let existing: Option<&Value> = all_workspaces.iter().find(|w| num_of(w) == Some(num));
+        skip.number(num) || existing.is_some_and(|w| skip.workspace(w) || empty(w) || grouped_out(w)) || (existing.is_none() && args.skip_empty)
+    };
+    let workspaces: &Vec<Value> = &all_workspaces.iter()
+        .filter(|w| w["focused"] == true || w["id"] == current_ws["id"] || !(archive::is_archived(&config, w) || skip.workspace(w) || empty(w) || grouped_out(w)))
+        .cloned()
+        .collect();
+
+    let current_ws_num: i64 = num_of(current_ws).unwrap_or(0);
+    let current_ws_name: &str = current_ws["name"].as_str().unwrap_or_default();
+    let current_output: &str = current_ws["output"].as_str().unwrap_or_default();
+
+    if args.push || matches!(args.action, Action::Push) {
+        state::update(|state| state.stack.push(state::WorkspaceRef::from_value(current_ws))).map_err(Error::Failed)?;
+
+        if let Action::Push = args.action {
+            return Ok(());
+        }
+    }
+
+    // Numbers sway puts on other outputs by itself once they get created
+    let reserved: Vec<i64> = assignments.iter()
+        .filter(|a| a.output(&outputs).is_some_and(|o| o != current_output))
+        .filter_map(assign::Assignment::num)
+        .collect();
+
+    let mut plan: Plan = Plan::from_args(&args);
+    let mut found_window: Option<i64> = None;
+    let mut template: Option<&config::Template> = None;
+    if current_ws["id"] != focused_ws["id"] {
+        plan.origin = Some(current_output.to_string());
+    }
+    // A focused workspace has no window to move, the move then goes without criteria
+    let focused_con: Option<i64> = if plan.focus_moved {
+        tree.as_ref().and_then(tree::find_focused).filter(|c| c.kind == "con" || c.kind == "floating_con").map(|c| c.id)
+    } else {
+        None
+    };
+
+    // Refuse to leave a fullscreen window behind, before any command is sent
+    let guard = |target: Option<&Target>| -> Result<(), Error> {
+        if !plan.focus_workspace || target.is_some_and(|t| t.matches(focused_ws)) {
+            return Ok(());
+        }
+        let name: &str = focused_ws["name"].as_str().unwrap_or_default();
+        match tree.as_ref().and_then(|t| tree::find_workspace(t, name)) {
+            Some(workspace) if tree::has_fullscreen(workspace) => {
+                Err(Error::Failed(format!("workspace {name} shows a fullscreen window, use --force to leave it")))
+            },
+            _ => Ok(()),
+        }
+    };
+    let guarded: bool = config.guard.fullscreen && !args.force;
+
+    // These actions already switch or rename while working out the target
+    let switches_early: bool = args.global && matches!(args.action, Action::Next | Action::Prev | Action::Goto)
+        || matches!(args.action, Action::SyncGoto | Action::Set | Action::MoveAll | Action::Insert);
+    if guarded && switches_early {
+        guard(None)?;
+    }
+
+    // Stepping past either end of the existing workspaces goes round to the other end with --wrap
+    let by_name: bool = args.order.unwrap_or(config.navigation.order) == Order::Name;
+    let wrap: bool = args.wrap.unwrap_or(config.navigation.wrap);
+    let by_number = |workspaces: &[Value], current: &Value, step: i64| {
+        let scope: Vec<&Value> = workspaces.iter().collect();
+        if by_name {
+            return step_by_name(&scope, current, step, wrap);
+        }
+        wrap.then(|| wrapped(&scope, current, step)).flatten()
+            .unwrap_or_else(|| step_through(&scope, current, step, |num| step_over(skipped, num, |n| find_by(workspaces, n, step))))
+    };
+    let range: Option<&config::NumberRange> = output::lookup(&config.ranges, &outputs, current_output);
+    let on_output = |workspaces: &[Value], current: &Value, step: i64| {
+        let scope: Vec<&Value> = workspaces.iter().filter(|w| w["output"] == current_output).collect();
+        if by_name {
+            return step_by_name(&scope, current, step, wrap);
+        }
+        wrap.then(|| wrapped(&scope, current, step)).flatten()
+            .unwrap_or_else(|| step_through(&scope, current, step, |num| {
+                // Stepping out of the output's number range stays put
+                let next: i64 = step_over(skipped, num, |n| find_on_output(workspaces, n, step, current_output, &reserved));
+                if range.is_some_and(|r| !r.contains(next)) { num } else { next }
+            }))
+    };
+    // Layout-aware steps tour the workspaces output by output in reading order, going round from the
+    // last workspace of the last output to the first of the first only with --wrap
+    let layout_aware: bool = (args.layout_aware || config.navigation.layout_aware) && !args.global;
+    let tour_outputs: Vec<Output> = if layout_aware { output::tour(&outputs, &config.outputs) } else { Vec::new() };
+    let across_outputs = |workspaces: &[Value], current: &Value, step: i64| {
+        let mut tour: Vec<&Value> = Vec::new();
+        for output in &tour_outputs {
+            let mut on: Vec<&Value> = workspaces.iter().filter(|w| w["output"] == output.name.as_str()).collect();
+            if by_name {
+                on.sort_by_key(|w| natural_key(w["name"].as_str().unwrap_or_default()));
+            } else {
+                on.sort_by_key(|w| order_key(w));
+            }
+            tour.extend(on);
+        }
+        let Some(at) = tour.iter().position(|w| w["id"] == current["id"]) else {
+            return Target::of(current);
+        };
+        let last: i64 = tour.len() as i64 - 1;
+        let next: i64 = match at as i64 + step {
+            n if wrap => n.rem_euclid(last + 1),
+            n => n.clamp(0, last),
+        };
+        Target::of(tour[next as usize])
+    };
+    let steps = |step: &dyn Fn(&[Value], &Value) -> Target| repeat(workspaces, current_ws, current_output, args.count, step);
 
-    let workspaces: &Vec<Value> = &get_workspaces(&mut client);
+    // Numbers no workspace has, for the actions that open a fresh one
+    let used: Vec<i64> = all_workspaces.iter().filter_map(num_of).collect();
+    let free = |n: &i64| !used.contains(n) && !skipped(*n);
+
+    let target: Target = match args.action {
+        Action::NextOnOutput => steps(&|w, current| on_output(w, current, 1)),
+        Action::PrevOnOutput => steps(&|w, current| on_output(w, current, -1)),
+        Action::NextOutput | Action::PrevOutput => {
+            let step: i64 = if let Action::NextOutput = args.action { 1 } else { -1 };
+            find_output(&outputs, workspaces, current_ws, step, current_output, args.wrap.unwrap_or(config.navigation.wrap_outputs))
+        },
+        Action::Next if args.global => Target::Number(global_step(client, &config, &outputs, current_ws_num, args.count as i64)?),
+        Action::Prev if args.global => Target::Number(global_step(client, &config, &outputs, current_ws_num, -(args.count as i64))?),
+        Action::Next if layout_aware => steps(&|w, current| across_outputs(w, current, 1)),
+        Action::Prev if layout_aware => steps(&|w, current| across_outputs(w, current, -1)),
+        Action::Next => steps(&|w, current| by_number(w, current, 1)),
+        Action::Prev => steps(&|w, current| by_number(w, current, -1)),
+        Action::SyncGoto => {
+            let num: i64 = require_number(&args, "sync-goto requires a workspace number")?;
+            Target::Number(sync_goto(client, &config, &outputs, num)?)
+        },
+        Action::GotoLocal => {
+            let num: i64 = require_number(&args, "goto-local requires a workspace number")?;
+            if !(1..=9).contains(&num) {
+                return Err(Error::usage(ErrorKind::InvalidValue, "goto-local takes a number from 1 to 9"));
+            }
+            let index: usize = outputs.iter().position(|o| o.name == current_output).unwrap_or(0);
+            Target::Number(config.offset(&outputs, index) + num)
+        },
+        Action::Goto if args.global => {
+            let num: i64 = require_number(&args, "goto --global requires a workspace index")?;
+            Target::Number(sync_goto(client, &config, &outputs, num)?)
+        },
+        Action::Goto => {
+            let workspace: &str = require_param(&args, 0, "goto requires a workspace")?;
+            let target: Target = match workspace.parse::<i64>() {
+                Ok(num) if num >= 0 => Target::Number(num),
+                _ => Target::Name(workspace.to_string()),
+            };
+            if !args.create && !all_workspaces.iter().any(|w| target.matches(w)) {
+                return Err(Error::Failed(format!("no workspace {workspace}, --create makes it")));
+            }
+            target
+        },
+        Action::New | Action::Isolate => Target::Number((1..).find(free).unwrap_or_default()),
+        Action::NextFree => Target::Number((current_ws_num + 1..).find(free).unwrap_or_default()),
+        Action::Insert => {
+            // Every later workspace moves up by one to make room
+            let num: i64 = numbered(current_ws)?;
+            let renames: Vec<renumber::Rename> = all_workspaces.iter()
+                .filter(|w| num_of(w).is_some_and(|n| n > num))
+                .filter_map(|w| Some((w["name"].as_str()?.to_string(), renumber::with_number(w["name"].as_str()?, num_of(w)? + 1))))
+                .collect();
+            let commands: Vec<String> = renumber::commands(&renames);
+            confirm(&args, &commands)?;
+            if !commands.is_empty() {
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            Target::Number(num + 1)
+        },
+        Action::NewOnOutput => {
+            // Inside the output's range, or else after the numbers the output has
+            let free = |n: &i64| free(n) && !reserved.contains(n);
+            let found: Option<i64> = match range {
+                Some(range) => (range.first..=range.last).find(free),
+                None => {
+                    let after: i64 = all_workspaces.iter().filter(|w| w["output"] == current_output).filter_map(num_of).max().unwrap_or_default();
+                    (after + 1..).find(free)
+                },
+            };
+            match found {
+                Some(num) => Target::Number(num),
+                None => return Err(Error::Failed(format!("no free workspace number left in the range of {current_output}"))),
+            }
+        },
+        Action::Urgent => {
+            // The nearest in navigation order, on the same output first
+            let mut ordered: Vec<&Value> = all_workspaces.iter().collect();
+            ordered.sort_by_key(|w| order_key(w));
+            let at: usize = ordered.iter().position(|w| w["id"] == current_ws["id"]).unwrap_or_default();
+            let nearest: Option<&Value> = ordered.iter().enumerate()
+                .filter(|(_, w)| w["urgent"] == true && w["id"] != current_ws["id"])
+                .min_by_key(|(i, w)| (w["output"] != current_output, i.abs_diff(at)))
+                .map(|(_, w)| *w);
+            match nearest {
+                Some(workspace) => Target::of(workspace),
+                None => return Err(Error::Failed("no urgent workspace".to_string())),
+            }
+        },
+        Action::GotoWindow => {
+            // The first match in tree order on a workspace of an output, which leaves out the scratchpad
+            let found: Option<(&tree::Node, &Value)> = tree.as_ref().and_then(|t| {
+                tree::windows(t).into_iter()
+                    .filter(|(window, _)| args.criteria.matches(window))
+                    .find_map(|(window, _)| {
+                        let name: Option<&str> = tree::workspace_of(t, window.id)?.name.as_deref();
+                        Some((window, all_workspaces.iter().find(|w| w["name"].as_str() == name)?))
+                    })
+            });
+            let Some((window, workspace)) = found else {
+                return Err(Error::Failed("no window matches".to_string()));
+            };
+            if args.focus_window {
+                found_window = Some(window.id);
+            }
+            Target::of(workspace)
+        },
+        Action::GotoName => Target::Name(require_param(&args, 0, "goto-name requires a workspace name")?.to_string()),
+        Action::MoveAll => {
+            let Some(to) = args.to else {
+                return Err(Error::usage(ErrorKind::MissingRequiredArgument, "move-all requires --to <N>"));
+            };
+            let tree: &tree::Node = tree.as_ref().unwrap();
+            Target::Number(move_all(client, tree, current_ws_name, to)?)
+        },
+        Action::Bookmark => match bookmark(&args, &all_workspaces, current_ws)? {
+            Some(target) => target,
+            None => return Ok(()),
+        },
+        Action::Set => {
+            let usage = "set requires activate <NAME>";
+            if require_param(&args, 0, usage)? != "activate" {
+                return Err(Error::usage(ErrorKind::InvalidValue, usage));
+            }
+            let name: &str = require_param(&args, 1, usage)?;
+            match activate_set(client, &config, &outputs, &all_workspaces, tree.as_ref(), name, args.create)? {
+                Some(target) => target,
+                None => return Ok(()),
+            }
+        },
+        Action::Profile => {
+            let usage = "profile requires activate <NAME> or clear";
+            let (profile, set): (Option<&str>, Option<String>) = match require_param(&args, 0, usage)? {
+                "activate" => {
+                    let name: &str = require_param(&args, 1, usage)?;
+                    let Some(profile) = config.profiles.get(name) else {
+                        return Err(Error::Failed(format!("no profile {name}")));
+                    };
+                    (Some(name), profile.set.to_owned())
+                },
+                "clear" => (None, None),
+                _ => return Err(Error::usage(ErrorKind::InvalidValue, usage)),
+            };
+            state::update(|state| state.profile = profile.map(String::from)).map_err(Error::Failed)?;
+            let Some(set) = set else {
+                return Ok(());
+            };
+            match activate_set(client, &config, &outputs, &all_workspaces, tree.as_ref(), &set, args.create)? {
+                Some(target) => target,
+                None => return Ok(()),
+            }
+        },
+        Action::Open => {
+            let name: &str = require_param(&args, 0, "open requires a template name")?;
+            template = Some(config.templates.get(name).ok_or_else(|| Error::Failed(format!("no template {name}")))?);
+            Target::Number((1..).find(free).unwrap_or_default())
+        },
+        Action::Layout => {
+            layout(&args, client, tree.as_ref().unwrap(), chosen_workspace(&args, &all_workspaces, current_ws)?)?;
+            return Ok(());
+        },
+        Action::Pick => {
+            let entries: Vec<String> = picker::entries(&config, &state::load(), workspaces);
+            match picker::pick(&config, &entries).map_err(|e| Error::Failed(format!("{}: {e}", config.picker.command)))? {
+                Some(choice) => Target::Name(choice),
+                None => return Ok(()),
+            }
+        },
+        Action::Unarchive => {
+            let name: Option<&str> = args.params.first().map(String::as_str);
+            let commands: Vec<String> = archive::unarchive_commands(&config, &all_workspaces, name);
+            if commands.is_empty() {
+                return Err(Error::Failed(format!("no archived workspace {}", name.unwrap_or_default()).trim_end().to_string()));
+            }
+            confirm(&args, &commands)?;
+            ipc(client, ipc_command::run(commands.join("; ")))?;
+            match name {
+                Some(name) => Target::Name(name.to_string()),
+                None => return Ok(()),
+            }
+        },
+        Action::Swap => {
+            let workspace: &Value = workspace_by(&all_workspaces, &args.params[0])?;
+            let num: Option<i64> = match args.params.get(1) {
+                Some(other) => Some(match other.parse::<i64>() {
+                    Ok(num) => num,
+                    Err(_) => numbered(workspace_by(&all_workspaces, other)?)?,
+                }),
+                None => neighbour(workspaces, numbered(workspace)?, 1).and_then(num_of),
+            };
+            if let Some(num) = num {
+                swap(client, &all_workspaces, workspace, num)?;
+            }
+            return Ok(());
+        },
+        Action::ShiftLeft | Action::ShiftRight => {
+            let step: i64 = if let Action::ShiftLeft = args.action { -1 } else { 1 };
+            if let Some(num) = neighbour(workspaces, numbered(current_ws)?, step).and_then(num_of) {
+                swap(client, &all_workspaces, current_ws, num)?;
+            }
+            return Ok(());
+        },
+        Action::Rebalance => {
+            let commands: Vec<String> = rebalance::plan(&config.rebalance.weights, &outputs, &all_workspaces);
+            if !commands.is_empty() {
+                confirm(&args, &commands)?;
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::Balance => {
+            let pinned: Skip = Skip::new(&config.rebalance.pinned).map_err(Error::Failed)?;
+            let commands: Vec<String> = rebalance::balance(&config.rebalance.weights, &outputs, &config.ranges, &pinned, &all_workspaces);
+            if !commands.is_empty() {
+                confirm(&args, &commands)?;
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::SwapOutputs => {
+            let by_reference = |reference: &str| output::resolve(&outputs, reference).ok_or_else(|| Error::Failed(format!("no output {reference}")));
+            let (a, b): (&Output, &Output) = match (args.params.first(), args.params.get(1)) {
+                (Some(a), Some(b)) => (by_reference(a)?, by_reference(b)?),
+                _ if outputs.len() < 2 => return Err(Error::Failed("swap-outputs needs two outputs".to_string())),
+                _ => {
+                    let at: usize = outputs.iter().position(|o| o.focused).unwrap_or_default();
+                    (&outputs[at], &outputs[(at + 1) % outputs.len()])
+                },
+            };
+            if a.name == b.name {
+                return Err(Error::Failed(format!("{} is both outputs", a.name)));
+            }
+            if let Some(commands) = rebalance::rotate(&[a, b], outputs.iter().find(|o| o.focused)) {
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::RotateOutputs => {
+            let mut ring: Vec<&Output> = outputs.iter().collect();
+            if args.reverse {
+                ring.reverse();
+            }
+            if let Some(commands) = rebalance::rotate(&ring, None).filter(|_| ring.len() > 1) {
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::MoveToOutput => {
+            let from: &Output = outputs.iter().find(|o| o.name == current_output).ok_or_else(|| Error::Failed(format!("no output {current_output}")))?;
+            let wanted: &str = require_param(&args, 0, "move-to-output requires a direction, output or number")?;
+            let to: &Output = match (args.direction, wanted.parse::<usize>()) {
+                (Some(direction), _) => output::neighbour(&outputs, from, direction, args.wrap == Some(true))
+                    .ok_or_else(|| Error::Failed(format!("no output {wanted} of {current_output}")))?,
+                // Counted from 1 like the screens on a desk
+                (None, Ok(index)) => index.checked_sub(1).and_then(|i| outputs.get(i))
+                    .ok_or_else(|| Error::Failed(format!("no output {index}, there are {}", outputs.len())))?,
+                (None, Err(_)) => output::resolve(&outputs, wanted).ok_or_else(|| Error::Failed(format!("no output {wanted}")))?,
+            };
+            let mut command: String = format!("move workspace to output {}", quote(&to.name));
+            if args.no_focus_ws {
+                command.push_str(&format!("; focus output {}", quote(&from.name)));
+            } else if args.warp_cursor && to.name != from.name {
+                let (x, y) = to.centre();
+                command.push_str(&format!("; seat - cursor set {x} {y}"));
+            }
+            ipc(client, ipc_command::run(command))?;
+            return Ok(());
+        },
+        Action::FocusOutput => {
+            let usage = "focus-output requires next|prev|left|right|up|down";
+            let at: usize = outputs.iter().position(|o| o.name == current_output).ok_or_else(|| Error::Failed(format!("no output {current_output}")))?;
+            // next and prev go round the outputs from left to right
+            let to: Option<&Output> = match (args.direction, require_param(&args, 0, usage)?) {
+                (Some(direction), _) => output::neighbour(&outputs, &outputs[at], direction, args.wrap == Some(true)),
+                (None, "next") => outputs.get((at + 1) % outputs.len()),
+                (None, "prev") => outputs.get((at + outputs.len() - 1) % outputs.len()),
+                _ => return Err(Error::usage(ErrorKind::InvalidValue, usage)),
+            };
+            match to.filter(|o| o.name != current_output) {
+                Some(to) => {
+                    let mut command: String = format!("focus output {}", quote(&to.name));
+                    if args.warp_cursor {
+                        let (x, y) = to.centre();
+                        command.push_str(&format!("; seat - cursor set {x} {y}"));
+                    }
+                    ipc(client, ipc_command::run(command))?
+                },
+                None => return Err(Error::Failed(format!("no output {} of {current_output}", args.params[0]))),
+            };
+            return Ok(());
+        },
+        Action::Gather => {
+            let commands: Vec<String> = rebalance::gather(&all_workspaces, current_output, args.hidden);
+            if !commands.is_empty() {
+                confirm(&args, &commands)?;
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::Compact => {
+            let per_output: bool = args.per_output || config.compact.per_output;
+            let pinned: Skip = Skip::new(&config.compact.pinned).map_err(Error::Failed)?;
+            let renames: Vec<renumber::Rename> = renumber::compact(&all_workspaces, &outputs, &config.ranges, &pinned, per_output);
+            let commands: Vec<String> = renumber::commands(&renames);
+            if !commands.is_empty() {
+                confirm(&args, &commands)?;
+                ipc(client, ipc_command::run(commands.join("; ")))?;
+            }
+            return Ok(());
+        },
+        Action::Query => {
+            let usage = "query requires prev-workspace";
+            if require_param(&args, 0, usage)? != "prev-workspace" {
+                return Err(Error::usage(ErrorKind::InvalidValue, usage));
+            }
+            let output: &str = args.output.as_deref().unwrap_or(current_output);
+            let state = state::load();
+            let previous: Option<Value> = state.previous.get(output).map(|p| match p.resolve(&all_workspaces) {
+                Some(workspace) => workspace.to_owned(),
+                None => json!({ "num": p.num, "name": p.name, "output": p.output }),
+            });
+            match (args.format, previous) {
+                (Format::Json, previous) => println!("{}", schema::document(json!({
+                    "output": output,
+                    "workspace": previous.map(|p| schema::workspace(&config, &state, &p)),
+                }))),
+                (Format::Text, Some(previous)) => println!("{}", previous["name"].as_str().unwrap_or_default()),
+                (Format::Text, None) => return Err(Error::Failed(format!("no previous workspace on {output}"))),
+            }
+            return Ok(());
+        },
+        Action::Shot => {
+            let Some(file) = &args.out else {
+                return Err(Error::usage(ErrorKind::MissingRequiredArgument, "shot requires --out <FILE>"));
+            };
+            let workspace: &Value = chosen_workspace(&args, &all_workspaces, current_ws)?;
+            shot::shot(client, &all_workspaces, workspace, file, args.reveal)?;
+            return Ok(());
+        },
+        Action::KillAll => {
+            let workspace: &Value = chosen_workspace(&args, &all_workspaces, current_ws)?;
+            return kill_all(&args, client, tree, workspace);
+        },
+        Action::Windows => {
+            let workspace: &Value = chosen_workspace(&args, &all_workspaces, current_ws)?;
+            let name: &str = workspace["name"].as_str().unwrap_or_default();
+            let windows: Vec<(&tree::Node, bool)> = tree.as_ref()
+                .and_then(|t| tree::find_workspace(t, name))
+                .map(tree::windows)
+                .unwrap_or_default();
+            match args.format {
+                Format::Json => println!("{}", schema::document(json!({
+                    "workspace": schema::workspace(&config, &state::load(), workspace),
+                    "windows": windows.iter().map(|(w, floating)| schema::window(w, *floating)).collect::<Vec<Value>>(),
+                }))),
+                Format::Text => for (window, floating) in windows {
+                    let rect = &window.rect;
+                    println!(
+                        "{}\t{}\t{}{}\t{}x{}+{}+{}\t{}",
+                        window.id,
+                        schema::app_id(window).unwrap_or("-"),
+                        if floating { 'F' } else { '-' },
+                        if window.fullscreen_mode > 0 { 'U' } else { '-' },
+                        rect.width, rect.height, rect.x, rect.y,
+                        window.name.as_deref().unwrap_or_default(),
+                    );
+                },
+            }
+            return Ok(());
+        },
+        Action::Rename => {
+            let workspace: &Value = chosen_workspace(&args, &all_workspaces, current_ws)?;
+            let name: &str = workspace["name"].as_str().unwrap_or_default();
+            let renamed: String = match (num_of(workspace), args.params.first()) {
+                (Some(num), Some(label)) => format!("{num}:{label}"),
+                (Some(num), None) => num.to_string(),
+                (None, Some(label)) => label.to_owned(),
+                (None, None) => return Err(Error::Failed(format!("workspace {name} has no number to keep"))),
+            };
+            if renamed != name {
+                ipc(client, ipc_command::run(format!("rename workspace {} to {}", quote(name), quote(&renamed))))?;
+            }
+            return Ok(());
+        },
+        Action::Label => {
+            label(&args, &all_workspaces)?;
+            return Ok(());
+        },
+        Action::Group => match group(&args, current_ws, &all_workspaces)? {
+            Some(target) => target,
+            None => return Ok(()),
+        },
+        Action::Cwd => {
+            project::export(&config, current_ws).map_err(|e| Error::Failed(format!("{}: {e}", project::env_path().display())))?;
+            let dir: String = match project::project_dir(&config, current_ws) {
+                Some(dir) => dir.display().to_string(),
+                None => var("HOME").unwrap_or_default(),
+            };
+            match args.format {
+                Format::Text => println!("{dir}"),
+                Format::Json => println!("{}", schema::document(json!({
+                    "workspace": schema::workspace(&config, &state::load(), current_ws),
+                    "project_dir": dir,
+                }))),
+            }
+            return Ok(());
+        },
+        Action::BackAndForth => match state::load().focused_before {
+            Some(before) => Target::remembered(&before, &all_workspaces),
+            None => return Err(Error::Failed("no workspace was focused before this one".to_string())),
+        },
+        Action::Back | Action::Forward => {
+            let step: i64 = if let Action::Back = args.action { -1 } else { 1 };
+            let Some(entry) = state::update(|state| state.step_history(current_ws, step)).map_err(Error::Failed)? else {
+                let way: &str = if step < 0 { "back" } else { "forward" };
+                return Err(Error::Failed(format!("no workspace to go {way} to in the history")));
+            };
+            Target::remembered(&entry, &all_workspaces)
+        },
+        Action::Pop => {
+            let Some(pushed) = state::update(|state| state.stack.pop()).map_err(Error::Failed)? else {
+                return Err(Error::Failed("workspace stack is empty".to_string()));
+            };
+            Target::remembered(&pushed, &all_workspaces)
+        },
+        Action::Digit | Action::Mru | Action::Daemon | Action::Push | Action::Completions | Action::Schema | Action::Deck | Action::Follow | Action::Waybar | Action::I3bar | Action::Polybar => {
+            unreachable!()
+        },
+    };
 
-    let current_ws: &Value = workspaces.into_iter().filter(|w| w["focused"] == true).nth(0).unwrap();
-    let current_ws_num: i64 = current_ws["num"].as_i64().unwrap();
-    let current_output: String = current_ws["output"].to_string();
+    if guarded && !switches_early {
+        guard(Some(&target))?;
+    }
 
-    let num: i64 = match args.action {
-        Action::NextOnOutput => find_on_output(&workspaces, current_ws_num, 1, current_output),
-        Action::PrevOnOutput => find_on_output(&workspaces, current_ws_num, -1, current_output),
-        Action::NextOutput => find_output(&workspaces, current_ws_num, 1, current_output),
-        Action::PrevOutput => find_output(&workspaces, current_ws_num, -1, current_output),
-        Action::Next => find_by(&workspaces, current_ws_num, 1),
-        Action::Prev => find_by(&workspaces, current_ws_num, -1),
+    // The workspaces as they are after renaming them for the target
+    let renamed: Vec<Value>;
+    let all_workspaces: &[Value] = if let Action::Insert = args.action {
+        renamed = get_workspaces(client)?;
+        &renamed
+    } else {
+        &all_workspaces
     };
 
-    if args.move_ws {
-        move_ws(&mut client, num).unwrap();
+    if args.focus_last {
+        plan.land_on = all_workspaces.iter()
+            .find(|w| target.matches(w))
+            .and_then(|w| w["name"].as_str())
+            .and_then(|name| tree::find_workspace(tree.as_ref()?, name))
+            .and_then(tree::last_focused)
+            .map(|c| c.id);
+    }
+    if args.merge && !target.matches(current_ws) {
+        plan.merge = tree.as_ref()
+            .and_then(|t| tree::find_workspace(t, current_ws_name))
+            .map(|w| tree::children(w).map(|c| c.id).collect())
+            .unwrap_or_default();
+    }
+    if found_window.is_some() {
+        plan.land_on = found_window;
+    }
+    // Folding a workspace into another is a bulk change like the others
+    let merges: Vec<String> = plan.merge.iter().map(|id| format!("[con_id={id}] move container to workspace {target}")).collect();
+    confirm(&args, &merges)?;
+
+    if !all_workspaces.iter().any(|w| target.matches(w)) {
+        plan.create_on = args.create_on.as_deref()
+            .or_else(|| config.create_on.get(&target.label()).map(String::as_str))
+            .or_else(|| if let Target::Number(num) = target { range_owner(&config, &outputs, num) } else { None })
+            .or_else(|| matches!(args.action, Action::NewOnOutput).then_some(current_output))
+            .map(|reference| output::resolve(&outputs, reference).map_or(reference, |o| o.name.as_str()).to_string());
+    }
+
+    if args.warp_cursor && plan.focus_workspace {
+        // New workspaces show up on the output the action started from unless told or assigned otherwise,
+        // `workspace number N` creates a workspace called N
+        let output: &str = all_workspaces.iter()
+            .find(|w| target.matches(w))
+            .and_then(|w| w["output"].as_str())
+            .or(plan.create_on.as_deref())
+            .or_else(|| assignments.iter().find(|a| a.workspace == target.label())?.output(&outputs))
+            .unwrap_or(current_output);
+        if focused_ws["output"] != output {
+            plan.warp = outputs.iter().find(|o| o.name == output).map(Output::centre);
+        }
+    }
+
+    let name: String = all_workspaces.iter()
+        .find(|w| target.matches(w))
+        .and_then(|w| w["name"].as_str().map(String::from))
+        .unwrap_or_else(|| target.label());
+    let to_output: &str = all_workspaces.iter()
+        .find(|w| target.matches(w))
+        .and_then(|w| w["output"].as_str())
+        .or(plan.create_on.as_deref())
+        .unwrap_or(current_output);
+    let switch = hook::Switch { old: current_ws_name, old_output: current_output, new: &name, new_output: to_output };
+    let switches: bool = plan.focus_workspace && !target.matches(current_ws);
+    if switches {
+        hook::before(&config, &switch).map_err(Error::Failed)?;
+    }
+
+    plan.run(client, &target, focused_con)?;
+
+    if switches {
+        hook::after(&config, &switch);
+    }
+
+    if plan.focus_workspace {
+        let to: Value = all_workspaces.iter().find(|w| target.matches(w)).cloned()
+            .unwrap_or_else(|| {
+                let num: i64 = if let Target::Number(num) = target { num } else { -1 };
+                json!({ "num": num, "name": name, "output": to_output })
+            });
+        state::update(|state| {
+            state.record_visit(&name);
+            if !target.matches(current_ws) {
+                state.record_switch(current_ws, to_output);
+                state.record_history(current_ws, &to);
+            }
+        }).map_err(Error::Failed)?;
+    }
+
+    if let (Some(template), Target::Number(num)) = (template, &target) {
+        template::launch(client, template, *num)?;
     }
 
-    if !args.no_focus_ws {
-        focus_ws(&mut client, num).unwrap();
+    if plan.focus_workspace && !config.projects.is_empty() {
+        if let Some(focused) = get_workspaces(client)?.iter().find(|w| w["focused"] == true) {
+            project::export(&config, focused).map_err(|e| Error::Failed(format!("{}: {e}", project::env_path().display())))?;
+        }
     }
 
     if args.stdout_ws {
-        print!("{}", num);
+        match args.format {
+            Format::Text => print!("{}", target.label()),
+            Format::Json => {
+                let workspaces: Vec<Value> = get_workspaces(client)?;
+                let workspace: Value = workspaces.iter().find(|w| target.matches(w)).cloned()
+                    .unwrap_or_else(|| match &target {
+                        Target::Number(num) => json!({ "num": num, "name": num.to_string() }),
+                        Target::Name(name) => json!({ "name": name }),
+                    });
+                println!("{}", schema::document(json!({ "workspace": schema::workspace(&config, &state::load(), &workspace) })));
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if args_os().nth(1).is_some_and(|a| a == "__complete") {
+        let words: Vec<String> = args_os().skip(2).map(|a| a.to_string_lossy().to_string()).collect();
+        for candidate in completion::candidates(&cli::Cli::command(), &words) {
+            println!("{candidate}");
+        }
+        return;
+    }
+
+    let words: Vec<String> = args_os().skip(1).map(|a| a.to_string_lossy().to_string()).collect();
+    let mut args: Args = cli::parse(&words).unwrap_or_else(|e| e.exit());
+    args.interactive = true;
+
+    check_schema_version(&args).unwrap_or_else(|e| e.exit());
+
+    if let Action::Schema = args.action {
+        print!("{}", schema::SCHEMA);
+        return;
+    }
+
+    if let Action::Completions = args.action {
+        let shell: &str = require_param(&args, 0, "completions requires a shell").unwrap_or_else(|e| e.exit());
+        match completion::script(shell) {
+            Some(script) => print!("{script}"),
+            None => Error::usage(ErrorKind::InvalidValue, "completions supports bash, zsh and fish").exit(),
+        }
+        return;
+    }
+
+    // Actions that only make sense to the daemon, which keeps what they need between invocations
+    let forwarded: Option<String> = match args.action {
+        Action::Digit => Some(format!("digit {}", require_number(&args, "digit requires a digit").unwrap_or_else(|e| e.exit()))),
+        Action::Mru => Some(format!("mru {}", args.params[0])),
+        _ => None,
+    };
+    if let Some(line) = forwarded {
+        if let Err(err) = daemon::send(&line) {
+            Error::Failed(format!("cannot reach daemon at {}: {err}", daemon::socket_path().display())).exit();
+        }
+        return;
+    }
+
+    // Actions that print stay here, the daemon has no terminal to print to
+    let prints: bool = args.stdout_ws || matches!(args.action, Action::Query | Action::Cwd | Action::Windows);
+    if args.via_daemon && !prints && check_words(&words).is_ok() {
+        // The daemon says how the action went, without a daemon it runs here
+        if let Ok(done) = daemon::request(&shell_words::join(&words)) {
+            if let Err(message) = done {
+                Error::Failed(message).exit();
+            }
+            return;
+        }
+    }
+
+    let Some(sock) = args.sock.to_owned().or(config::load().sock).or_else(|| var("SWAYSOCK").ok()) else {
+        Error::Failed("no sway socket, set SWAYSOCK, `sock` in the config or --sock".to_string()).exit();
+    };
+    let mut client = Client::connect_to_path(sock).unwrap();
+
+    // Long-running actions keep the config as the file has it, and must not exit later on an edit that
+    // does not parse
+    let read = || config::read().unwrap_or_else(|err| Error::Failed(err).exit());
+
+    if let Action::Daemon = args.action {
+        if let Err(err) = daemon::run(&mut client, read()) {
+            err.exit();
+        }
+        return;
+    }
+
+    if let Action::Deck = args.action {
+        deck::run(&mut client, &read());
+        return;
+    }
+
+    if let Action::Waybar = args.action {
+        bar::waybar(&mut client, &read());
+        return;
+    }
+
+    if let Action::I3bar = args.action {
+        bar::i3bar(&mut client, &read(), args.blocks);
+        return;
+    }
+
+    if let Action::Polybar = args.action {
+        bar::polybar(&mut client, &read());
+        return;
+    }
+
+    if let Action::Follow = args.action {
+        follow::run(&client, &config::load());
+        return;
+    }
+
+    if let Err(err) = run(args, &mut client, config::load()) {
+        err.exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::{Target, find_by, find_output, natural_key, num_of, repeat, step_by_name, step_over, wrapped};
+    use crate::output::Output;
+
+    fn workspace(name: &str, num: i64) -> Value {
+        json!({ "name": name, "num": num, "output": "DP-1" })
+    }
+
+    fn output(name: &str, x: i64) -> Output {
+        Output { name: name.to_string(), x, y: 0, width: 1920, height: 1080, focused: false, current_workspace: None }
+    }
+
+    fn shown(name: &str, num: i64, output: &str) -> Value {
+        json!({ "name": name, "num": num, "output": output, "visible": true })
+    }
+
+    #[test]
+    fn natural_key_counts_digit_runs_by_value() {
+        assert!(natural_key("1:web") < natural_key("2:code"));
+        assert!(natural_key("2:code") < natural_key("10:chat"));
+        assert!(natural_key("a9") < natural_key("a10"));
+        assert!(natural_key("007") < natural_key("10"));
+    }
+
+    #[test]
+    fn wrapped_goes_round_at_either_end() {
+        let workspaces: Vec<Value> = vec![workspace("1", 1), workspace("3", 3), workspace("chat", -1)];
+        let scope: Vec<&Value> = workspaces.iter().collect();
+        assert_eq!(wrapped(&scope, &workspaces[2], 1), Some(Target::Number(1)));
+        assert_eq!(wrapped(&scope, &workspaces[0], -1), Some(Target::Name("chat".to_string())));
+    }
+
+    #[test]
+    fn wrapped_leaves_steps_inside_alone() {
+        let workspaces: Vec<Value> = vec![workspace("1", 1), workspace("3", 3), workspace("chat", -1)];
+        let scope: Vec<&Value> = workspaces.iter().collect();
+        assert_eq!(wrapped(&scope, &workspaces[1], 1), None);
+        assert_eq!(wrapped(&scope, &workspaces[1], -1), None);
+        assert_eq!(wrapped(&[], &workspaces[0], 1), None);
+    }
+
+    #[test]
+    fn repeat_starts_every_step_where_the_last_landed() {
+        let workspaces: Vec<Value> = vec![workspace("1", 1), workspace("2", 2), workspace("5", 5)];
+        let next = |w: &[Value], current: &Value| Target::Number(find_by(w, num_of(current).unwrap(), 1));
+        assert_eq!(repeat(&workspaces, &workspaces[0], "DP-1", 2, &next), Target::Number(3));
+        assert_eq!(repeat(&workspaces, &workspaces[0], "DP-1", 1, &next), Target::Number(2));
+    }
+
+    #[test]
+    fn repeat_steps_on_from_workspaces_it_creates() {
+        let workspaces: Vec<Value> = vec![workspace("1", 1), workspace("2", 2)];
+        let next = |w: &[Value], current: &Value| Target::Number(find_by(w, num_of(current).unwrap(), 1));
+        assert_eq!(repeat(&workspaces, &workspaces[1], "DP-1", 3, &next), Target::Number(5));
+    }
+
+    #[test]
+    fn step_by_name_follows_the_natural_order() {
+        let workspaces: Vec<Value> = vec![workspace("10:chat", 10), workspace("2:code", 2), workspace("mail", -1)];
+        let scope: Vec<&Value> = workspaces.iter().collect();
+        assert_eq!(step_by_name(&scope, &workspaces[1], 1, false), Target::Number(10));
+        assert_eq!(step_by_name(&scope, &workspaces[0], 1, false), Target::Name("mail".to_string()));
+        assert_eq!(step_by_name(&scope, &workspaces[0], -1, false), Target::Number(2));
+    }
+
+    #[test]
+    fn step_by_name_stops_at_either_end_unless_it_wraps() {
+        let workspaces: Vec<Value> = vec![workspace("10:chat", 10), workspace("2:code", 2), workspace("mail", -1)];
+        let scope: Vec<&Value> = workspaces.iter().collect();
+        assert_eq!(step_by_name(&scope, &workspaces[2], 1, false), Target::Name("mail".to_string()));
+        assert_eq!(step_by_name(&scope, &workspaces[2], 1, true), Target::Number(2));
+        assert_eq!(step_by_name(&scope, &workspaces[1], -1, true), Target::Name("mail".to_string()));
+    }
+
+    #[test]
+    fn find_output_wraps_past_the_last_output_only_with_wrap() {
+        let outputs: Vec<Output> = vec![output("DP-1", 0), output("DP-2", 1920), output("HDMI-A-1", 3840)];
+        let workspaces: Vec<Value> = vec![shown("1", 1, "DP-1"), shown("2", 2, "DP-2"), shown("3", 3, "HDMI-A-1")];
+        assert_eq!(find_output(&outputs, &workspaces, &workspaces[0], 1, "DP-1", false), Target::Number(2));
+        assert_eq!(find_output(&outputs, &workspaces, &workspaces[2], 1, "HDMI-A-1", false), Target::Number(3));
+        assert_eq!(find_output(&outputs, &workspaces, &workspaces[2], 1, "HDMI-A-1", true), Target::Number(1));
+        assert_eq!(find_output(&outputs, &workspaces, &workspaces[0], -1, "DP-1", true), Target::Number(3));
+    }
+
+    #[test]
+    fn step_over_passes_the_skipped_numbers() {
+        let skipped = |n: i64| n == 3 || n == 4;
+        assert_eq!(step_over(skipped, 2, |n| n + 1), 5);
+        assert_eq!(step_over(skipped, 5, |n| n - 1), 2);
+    }
+
+    #[test]
+    fn step_over_stays_put_when_only_skipped_numbers_are_left() {
+        assert_eq!(step_over(|n| n > 2, 2, |n| (n + 1).min(4)), 2);
     }
 }
@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+
+use clap::ValueEnum;
+use ksway::{Client, ipc_command};
+use serde::Deserialize;
+
+use crate::{Error, ipc, reply};
+use crate::config::{OutputSort, OutputsConfig};
+use crate::tree::Rect;
+
+
+/// A usable output and its position in the global layout, in logical coordinates
+#[derive(Clone, PartialEq, Eq)]
+pub struct Output {
+    pub name: String,
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+    pub focused: bool,
+    pub current_workspace: Option<String>,
+}
+
+/// The parts of an output in the `get_outputs` reply, everything else is skipped while parsing
+#[derive(Deserialize)]
+struct Reply {
+    name: String,
+    #[serde(default)]
+    active: bool,
+    /// False while DPMS has the output off, newer sway says `power` instead
+    #[serde(default = "on")]
+    dpms: bool,
+    #[serde(default = "on")]
+    power: bool,
+    #[serde(default)]
+    focused: bool,
+    /// In logical coordinates already, with the transform and the scale applied
+    #[serde(default)]
+    rect: Rect,
+    #[serde(default)]
+    current_workspace: Option<String>,
+}
+
+fn on() -> bool {
+    true
+}
+
+impl Reply {
+    /// Whether the output is enabled, powered and shows a workspace, the only ones worth going to
+    fn usable(&self) -> bool {
+        self.active && self.dpms && self.power && self.current_workspace.is_some()
+    }
+}
+
+impl From<Reply> for Output {
+    fn from(reply: Reply) -> Output {
+        Output {
+            name: reply.name,
+            x: reply.rect.x,
+            y: reply.rect.y,
+            width: reply.rect.width,
+            height: reply.rect.height,
+            focused: reply.focused,
+            current_workspace: reply.current_workspace,
+        }
+    }
+}
+
+impl Ord for Output {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.x, self.y, &self.name).cmp(&(other.x, other.y, &other.name))
+    }
+}
+
+impl PartialOrd for Output {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Usable outputs in layout order, by default left to right and then top to bottom, followed by
+/// `[outputs] order`. Disabled outputs, ones DPMS turned off and ones without a workspace are left out
+pub fn get_outputs(client: &mut Client, config: &OutputsConfig) -> Result<Vec<Output>, Error> {
+    let replies: Vec<Reply> = reply(&ipc(client, ipc_command::get_outputs())?)?;
+    let outputs: Vec<Output> = replies.into_iter()
+        .filter(Reply::usable)
+        .map(Output::from)
+        .collect();
+    Ok(arrange(sort(outputs, config.sort), &config.order))
+}
+
+fn sort(mut outputs: Vec<Output>, sort: OutputSort) -> Vec<Output> {
+    match sort {
+        OutputSort::X => outputs.sort(),
+        OutputSort::Y => outputs.sort_by(|a, b| (a.y, a.x, &a.name).cmp(&(b.y, b.x, &b.name))),
+        OutputSort::Serpentine => {
+            return rows(outputs).into_iter().enumerate()
+                .flat_map(|(index, mut row)| {
+                    if index % 2 == 1 {
+                        row.reverse();
+                    }
+                    row
+                })
+                .collect();
+        },
+    }
+    outputs
+}
+
+/// The outputs row by row from the top, each row left to right. An output starting above the bottom
+/// of the first output of a row belongs to that row, so a vertical stack is a row per output
+fn rows(mut outputs: Vec<Output>) -> Vec<Vec<Output>> {
+    outputs.sort_by(|a, b| (a.y, a.x, &a.name).cmp(&(b.y, b.x, &b.name)));
+    let mut rows: Vec<Vec<Output>> = Vec::new();
+    for output in outputs {
+        match rows.last_mut() {
+            Some(row) if output.y < row[0].y + row[0].height => row.push(output),
+            _ => rows.push(vec![output]),
+        }
+    }
+    for row in &mut rows {
+        row.sort();
+    }
+    rows
+}
+
+/// The order layout-aware navigation goes from output to output in: the one `[outputs]` sets, or when
+/// it sets none, reading order, so from the top left output of a grid on to the right and the row below
+pub fn tour(outputs: &[Output], config: &OutputsConfig) -> Vec<Output> {
+    if config.sort == OutputSort::X && config.order.is_empty() {
+        rows(outputs.to_vec()).concat()
+    } else {
+        outputs.to_vec()
+    }
+}
+
+/// Put the outputs `order` names first and in its order, keeping the others in the order they have
+fn arrange(outputs: Vec<Output>, order: &[String]) -> Vec<Output> {
+    let ranks: Vec<usize> = outputs.iter()
+        .map(|o| order.iter().position(|r| resolve(&outputs, r).is_some_and(|named| named.name == o.name)).unwrap_or(order.len()))
+        .collect();
+    let mut ranked: Vec<(usize, Output)> = ranks.into_iter().zip(outputs).collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, o)| o).collect()
+}
+
+/// The output a config entry refers to: a connector name, or `leftmost`, `rightmost`, `primary` (the one
+/// at the origin of the layout) or `internal` (the built-in panel) so that one config fits several machines
+pub fn resolve<'a>(outputs: &'a [Output], reference: &str) -> Option<&'a Output> {
+    match reference {
+        "leftmost" => outputs.iter().min(),
+        "rightmost" => outputs.iter().max(),
+        "primary" => outputs.iter().find(|o| o.x == 0 && o.y == 0).or(outputs.iter().min()),
+        "internal" => outputs.iter().find(|o| ["eDP", "LVDS", "DSI"].iter().any(|p| o.name.starts_with(p))),
+        name => outputs.iter().find(|o| o.name == name),
+    }
+}
+
+/// Which way to look for a neighbouring output
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Output {
+    pub fn centre(&self) -> (i64, i64) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+/// The output next to `from` in `direction`: the closest one that way, those sharing a stretch of edge
+/// with `from` first; with `wrap` and none that way the one furthest the other way
+pub fn neighbour<'a>(outputs: &'a [Output], from: &Output, direction: Direction, wrap: bool) -> Option<&'a Output> {
+    let (cx, cy) = from.centre();
+    // Distance ahead along the direction, distance across it, and whether the rects face each other
+    let measure = |o: &Output| {
+        let (ox, oy) = o.centre();
+        let overlaps_x: bool = o.x < from.x + from.width && from.x < o.x + o.width;
+        let overlaps_y: bool = o.y < from.y + from.height && from.y < o.y + o.height;
+        match direction {
+            Direction::Left => (cx - ox, (oy - cy).abs(), overlaps_y),
+            Direction::Right => (ox - cx, (oy - cy).abs(), overlaps_y),
+            Direction::Up => (cy - oy, (ox - cx).abs(), overlaps_x),
+            Direction::Down => (oy - cy, (ox - cx).abs(), overlaps_x),
+        }
+    };
+    let others = || outputs.iter().filter(|o| o.name != from.name).map(|o| (o, measure(o)));
+
+    let ahead = others().filter(|(_, (along, _, _))| *along > 0)
+        .min_by_key(|(_, (along, across, facing))| (!facing, *along, *across));
+    let around = || others().filter(|(_, (along, _, _))| *along < 0)
+        .min_by_key(|(_, (along, across, facing))| (!facing, *along, *across));
+    ahead.or_else(|| around().filter(|_| wrap)).map(|(o, _)| o)
+}
+
+/// Entry of a map keyed by output references for `output`, its connector name wins over symbolic keys
+pub fn lookup<'a, T>(
+    entries: impl IntoIterator<Item = (&'a String, &'a T)>, outputs: &[Output], output: &str,
+) -> Option<&'a T> {
+    let entries: Vec<(&String, &T)> = entries.into_iter().collect();
+    entries.iter().find(|(key, _)| *key == output)
+        .or_else(|| entries.iter().find(|(key, _)| resolve(outputs, key).is_some_and(|o| o.name == output)))
+        .map(|(_, value)| *value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Output, neighbour, rows, sort, tour};
+    use crate::config::{OutputSort, OutputsConfig};
+
+    fn output(name: &str, x: i64, y: i64) -> Output {
+        Output { name: name.to_string(), x, y, width: 1920, height: 1080, focused: false, current_workspace: None }
+    }
+
+    fn names(outputs: &[Output]) -> Vec<&str> {
+        outputs.iter().map(|o| o.name.as_str()).collect()
+    }
+
+    fn grid() -> Vec<Output> {
+        vec![output("bottom-right", 1920, 1080), output("top-left", 0, 0), output("bottom-left", 0, 1080), output("top-right", 1920, 0)]
+    }
+
+    #[test]
+    fn sort_by_x_goes_column_by_column() {
+        assert_eq!(names(&sort(grid(), OutputSort::X)), ["top-left", "bottom-left", "top-right", "bottom-right"]);
+    }
+
+    #[test]
+    fn sort_by_y_goes_row_by_row() {
+        assert_eq!(names(&sort(grid(), OutputSort::Y)), ["top-left", "top-right", "bottom-left", "bottom-right"]);
+    }
+
+    #[test]
+    fn sort_serpentine_turns_round_every_row() {
+        assert_eq!(names(&sort(grid(), OutputSort::Serpentine)), ["top-left", "top-right", "bottom-right", "bottom-left"]);
+    }
+
+    #[test]
+    fn rows_put_a_vertical_stack_in_a_row_each() {
+        let stack: Vec<Output> = vec![output("lower", 0, 1080), output("upper", 0, 0)];
+        let rows: Vec<Vec<Output>> = rows(stack);
+        assert_eq!(rows.iter().map(|row| names(row)).collect::<Vec<_>>(), [["upper"], ["lower"]]);
+    }
+
+    #[test]
+    fn rows_keep_outputs_beside_a_tall_one_in_its_row() {
+        let portrait = Output { width: 1080, height: 1920, ..output("portrait", 0, 0) };
+        let mixed: Vec<Output> = vec![output("right-lower", 1080, 1080), portrait, output("right-upper", 1080, 0)];
+        let rows: Vec<Vec<Output>> = rows(mixed);
+        assert_eq!(rows.iter().map(|row| names(row)).collect::<Vec<_>>(), [["portrait", "right-upper", "right-lower"]]);
+    }
+
+    #[test]
+    fn tour_reads_a_grid_row_by_row_unless_the_config_orders_outputs() {
+        assert_eq!(names(&tour(&sort(grid(), OutputSort::X), &OutputsConfig::default())), [
+            "top-left", "top-right", "bottom-left", "bottom-right",
+        ]);
+        let config = OutputsConfig { order: vec!["bottom-right".to_string()], sort: OutputSort::X };
+        assert_eq!(names(&tour(&grid(), &config)), names(&grid()));
+    }
+
+    #[test]
+    fn neighbour_prefers_outputs_sharing_an_edge() {
+        // The corner one is closer to the right, but below the edge of the left one
+        let outputs: Vec<Output> = vec![output("left", 0, 0), output("right", 1920, 0), output("corner", 1500, 1080)];
+        let name = |direction: Direction| neighbour(&outputs, &outputs[0], direction, false).map(|o| o.name.as_str());
+        assert_eq!(name(Direction::Right), Some("right"));
+        assert_eq!(name(Direction::Down), Some("corner"));
+        assert_eq!(name(Direction::Left), None);
+    }
+
+    #[test]
+    fn neighbour_wraps_to_the_furthest_output_the_other_way() {
+        let outputs: Vec<Output> = vec![output("left", 0, 0), output("middle", 1920, 0), output("right", 3840, 0)];
+        let name = |direction: Direction| neighbour(&outputs, &outputs[2], direction, true).map(|o| o.name.as_str());
+        assert_eq!(name(Direction::Left), Some("middle"));
+        assert_eq!(name(Direction::Right), Some("left"));
+    }
+}
@@ -0,0 +1,47 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::state::State;
+
+
+/// Workspace names and project workspaces, most frecent first, tags follow the name after a tab
+pub fn entries(config: &Config, state: &State, workspaces: &[Value]) -> Vec<String> {
+    let mut entries: Vec<(String, i64)> = workspaces.iter()
+        .map(|w| (w["name"].as_str().unwrap_or_default().to_string(), w["num"].as_i64().unwrap_or(-1)))
+        .collect();
+    for project in config.projects.keys() {
+        if !entries.iter().any(|(name, num)| name == project || num.to_string() == *project) {
+            entries.push((project.to_owned(), project.parse().unwrap_or(-1)));
+        }
+    }
+
+    entries.sort_by_key(|(name, num)| (std::cmp::Reverse(state.frecency(name)), *num < 0, *num, name.to_owned()));
+    entries.into_iter()
+        .map(|(name, _)| match state.labels.get(&name).filter(|l| !l.tags.is_empty()) {
+            Some(label) => format!("{name}\t{}", label.tags.iter().map(|t| format!("#{t}")).collect::<Vec<_>>().join(" ")),
+            None => name,
+        })
+        .collect()
+}
+
+/// Let the user choose one of `entries` with the configured dmenu-like command
+pub fn pick(config: &Config, entries: &[String]) -> std::io::Result<Option<String>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&config.picker.command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(entries.join("\n").as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let choice: String = stdout.split('\t').next().unwrap_or_default().trim().to_string();
+    Ok((output.status.success() && !choice.is_empty()).then_some(choice))
+}
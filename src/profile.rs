@@ -0,0 +1,102 @@
+use std::mem::zeroed;
+use std::ptr::null_mut;
+
+use crate::config::{Config, Profile};
+use crate::state::State;
+
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Local day of the week, Monday being 0, and minute of the day
+fn local_time() -> (usize, u32) {
+    // SAFETY: localtime_r only writes to the tm it is given
+    let tm: libc::tm = unsafe {
+        let now: libc::time_t = libc::time(null_mut());
+        let mut tm: libc::tm = zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    ((tm.tm_wday as usize + 6) % 7, (tm.tm_hour * 60 + tm.tm_min) as u32)
+}
+
+fn minutes(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Whether `HH:MM-HH:MM` covers the minute of the day, ranges ending before they start run past midnight
+fn covers(hours: &str, minute: u32) -> bool {
+    let Some((start, end)) = hours.split_once('-').and_then(|(s, e)| Some((minutes(s)?, minutes(e)?))) else {
+        return false;
+    };
+    if start <= end {
+        (start..end).contains(&minute)
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+impl Profile {
+    fn scheduled_at(&self, day: usize, minute: u32) -> bool {
+        self.hours.as_deref().is_some_and(|h| covers(h, minute))
+            && (self.days.is_empty() || self.days.iter().any(|d| d.eq_ignore_ascii_case(DAYS[day])))
+    }
+}
+
+/// The first profile whose hours cover the current time
+pub fn scheduled(config: &Config) -> Option<&str> {
+    let (day, minute) = local_time();
+    config.profiles.iter()
+        .find(|(_, p)| p.scheduled_at(day, minute))
+        .map(|(name, _)| name.as_str())
+}
+
+/// The profile activated by hand, or else the scheduled one
+pub fn active<'a>(config: &'a Config, state: &'a State) -> Option<&'a str> {
+    state.profile.as_deref()
+        .filter(|p| config.profiles.contains_key(*p))
+        .or_else(|| scheduled(config))
+}
+
+/// Lay the settings of the active profile over the top-level ones
+pub fn apply(config: &mut Config, state: &State) {
+    let Some(profile) = active(config, state).and_then(|p| config.profiles.get(p)) else {
+        return;
+    };
+    let (offsets, ranges) = (profile.offsets.to_owned(), profile.ranges.to_owned());
+    let (compact_pinned, rebalance_pinned) = (profile.compact_pinned.to_owned(), profile.rebalance_pinned.to_owned());
+    config.offsets.extend(offsets);
+    config.ranges.extend(ranges);
+    config.compact.pinned.extend(compact_pinned);
+    config.rebalance.pinned.extend(rebalance_pinned);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::covers;
+
+    #[test]
+    fn covers_same_day_hours() {
+        assert!(covers("09:00-17:00", 9 * 60));
+        assert!(covers("09:00-17:00", 16 * 60 + 59));
+        assert!(!covers("09:00-17:00", 17 * 60));
+        assert!(!covers("09:00-17:00", 8 * 60 + 59));
+    }
+
+    #[test]
+    fn covers_hours_past_midnight() {
+        assert!(covers("22:00-06:00", 23 * 60));
+        assert!(covers("22:00-06:00", 0));
+        assert!(covers("22:00-06:00", 5 * 60 + 59));
+        assert!(!covers("22:00-06:00", 6 * 60));
+        assert!(!covers("22:00-06:00", 12 * 60));
+    }
+
+    #[test]
+    fn covers_nothing_when_malformed() {
+        assert!(!covers("9-17", 10 * 60));
+        assert!(!covers("25:00-06:00", 60));
+        assert!(!covers("", 0));
+    }
+}
@@ -0,0 +1,43 @@
+use std::env::var;
+use std::fs::write;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::state::runtime_dir;
+
+
+/// Expand a leading `~` to the home directory
+fn expand(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{rest}", var("HOME").unwrap_or_default()))
+        },
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Project directory of a workspace, looked up by full name, then by number
+pub fn project_dir(config: &Config, workspace: &Value) -> Option<PathBuf> {
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+    let num: Option<String> = workspace["num"].as_i64().filter(|n| *n >= 0).map(|n| n.to_string());
+    config.projects.get(name)
+        .or_else(|| num.and_then(|n| config.projects.get(&n)))
+        .map(|dir| expand(dir))
+}
+
+pub fn env_path() -> PathBuf {
+    runtime_dir().join("sway-workspace.env")
+}
+
+/// Write the project directory of the focused workspace to a file shells and hooks can source
+pub fn export(config: &Config, workspace: &Value) -> std::io::Result<()> {
+    let dir: String = project_dir(config, workspace).map(|d| d.display().to_string()).unwrap_or_default();
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+    write(env_path(), format!(
+        "SWS_WORKSPACE={}\nSWS_PROJECT_DIR={}\n",
+        shell_words::quote(name),
+        shell_words::quote(&dir),
+    ))
+}
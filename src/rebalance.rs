@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::config::NumberRange;
+use crate::output::{self, Output};
+use crate::skip::Skip;
+use crate::{num_of, order_key, quote};
+
+
+/// How many of `total` workspaces each output gets, proportional to the weights with the remainders
+/// going to the outputs that lost the most to rounding
+fn shares(weights: &[u64], total: usize) -> Vec<usize> {
+    let sum: u64 = weights.iter().sum();
+    if sum == 0 {
+        return vec![0; weights.len()];
+    }
+    let exact: Vec<f64> = weights.iter().map(|w| *w as f64 * total as f64 / sum as f64).collect();
+    let mut shares: Vec<usize> = exact.iter().map(|e| e.floor() as usize).collect();
+
+    let mut by_remainder: Vec<usize> = (0..weights.len()).collect();
+    by_remainder.sort_by(|a, b| (exact[*b] - exact[*b].floor()).total_cmp(&(exact[*a] - exact[*a].floor())));
+    for index in by_remainder.into_iter().take(total - shares.iter().sum::<usize>()) {
+        shares[index] += 1;
+    }
+    shares
+}
+
+/// Come back to the workspace focused before `commands` moved workspaces around
+fn refocus(workspaces: &[Value], commands: &mut Vec<String>) {
+    if let Some(focused) = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str()) {
+        if !commands.is_empty() {
+            commands.push(format!("workspace {}", quote(focused)));
+        }
+    }
+}
+
+fn weights_of(weights: &HashMap<String, u64>, outputs: &[Output]) -> Vec<u64> {
+    outputs.iter().map(|o| output::lookup(weights, outputs, &o.name).copied().unwrap_or(1)).collect()
+}
+
+/// Commands that hand out the workspaces in number order to the outputs in layout order, each output
+/// getting a share proportional to its weight (1 when not configured)
+pub fn plan(weights: &HashMap<String, u64>, outputs: &[Output], workspaces: &[Value]) -> Vec<String> {
+    let mut ordered: Vec<&Value> = workspaces.iter().collect();
+    ordered.sort_by_key(|w| order_key(w));
+
+    let weights: Vec<u64> = weights_of(weights, outputs);
+    let destinations = outputs.iter().zip(shares(&weights, ordered.len()))
+        .flat_map(|(output, share)| std::iter::repeat_n(output, share));
+
+    let mut commands: Vec<String> = ordered.into_iter().zip(destinations)
+        .filter(|(w, output)| w["output"] != output.name.as_str())
+        .filter_map(|(w, output)| {
+            let name: &str = w["name"].as_str()?;
+            Some(format!("workspace {}; move workspace to output {}", quote(name), quote(&output.name)))
+        })
+        .collect();
+
+    refocus(workspaces, &mut commands);
+    commands
+}
+
+/// Commands that move the workspaces of the other outputs to `output`, with `hidden` only those their
+/// output does not show
+pub fn gather(workspaces: &[Value], output: &str, hidden: bool) -> Vec<String> {
+    let mut ordered: Vec<&Value> = workspaces.iter()
+        .filter(|w| w["output"] != output && !(hidden && w["visible"] == true))
+        .collect();
+    ordered.sort_by_key(|w| order_key(w));
+
+    let mut commands: Vec<String> = ordered.into_iter()
+        .filter_map(|w| Some(format!("workspace {}; move workspace to output {}", quote(w["name"].as_str()?), quote(output))))
+        .collect();
+    refocus(workspaces, &mut commands);
+    commands
+}
+
+/// Commands that even out how many workspaces the outputs have with as few moves as it takes: pinned
+/// workspaces stay, workspaces in the range of an output go there, and the rest stay where their
+/// output has room for them, lowest numbers first, the others going to the outputs furthest below
+/// their share
+pub fn balance(
+    weights: &HashMap<String, u64>, outputs: &[Output], ranges: &HashMap<String, NumberRange>, pinned: &Skip, workspaces: &[Value],
+) -> Vec<String> {
+    let mut ordered: Vec<&Value> = workspaces.iter().collect();
+    ordered.sort_by_key(|w| order_key(w));
+    let at = |w: &Value| outputs.iter().position(|o| w["output"] == o.name.as_str());
+    let ranged = |w: &Value| {
+        let num: i64 = num_of(w)?;
+        outputs.iter().position(|o| output::lookup(ranges, outputs, &o.name).is_some_and(|r| r.contains(num)))
+    };
+
+    let mut counts: Vec<usize> = vec![0; outputs.len()];
+    let mut destinations: Vec<(&Value, usize)> = Vec::new();
+    let mut free: Vec<(&Value, usize)> = Vec::new();
+    for workspace in ordered {
+        let Some(current) = at(workspace) else {
+            continue;
+        };
+        match ranged(workspace) {
+            _ if pinned.workspace(workspace) => destinations.push((workspace, current)),
+            Some(home) => destinations.push((workspace, home)),
+            None => free.push((workspace, current)),
+        }
+    }
+    destinations.iter().for_each(|(_, d)| counts[*d] += 1);
+
+    let targets: Vec<usize> = shares(&weights_of(weights, outputs), destinations.len() + free.len());
+    let mut moving: Vec<&Value> = Vec::new();
+    for (workspace, current) in free {
+        if counts[current] < targets[current] {
+            counts[current] += 1;
+            destinations.push((workspace, current));
+        } else {
+            moving.push(workspace);
+        }
+    }
+    for workspace in moving {
+        let Some(emptiest) = (0..outputs.len()).min_by_key(|i| counts[*i] as i64 - targets[*i] as i64) else {
+            break;
+        };
+        counts[emptiest] += 1;
+        destinations.push((workspace, emptiest));
+    }
+
+    let mut commands: Vec<String> = destinations.into_iter()
+        .filter(|(w, destination)| w["output"] != outputs[*destination].name.as_str())
+        .filter_map(|(w, destination)| {
+            Some(format!("workspace {}; move workspace to output {}", quote(w["name"].as_str()?), quote(&outputs[destination].name)))
+        })
+        .collect();
+    refocus(workspaces, &mut commands);
+    commands
+}
+
+/// Commands that hand the workspace each output of `ring` shows to the next one, the last one's to
+/// the first, leaving the focus on the output that has it
+pub fn rotate(ring: &[&Output], focused: Option<&Output>) -> Option<Vec<String>> {
+    let shown: Vec<String> = ring.iter().map(|o| o.current_workspace.as_deref().map(quote)).collect::<Option<_>>()?;
+    let show = |name: &str| format!("workspace --no-auto-back-and-forth {name}");
+    let mut commands: Vec<String> = Vec::new();
+    for (index, name) in shown.iter().enumerate() {
+        commands.push(show(name));
+        commands.push(format!("move workspace to output {}", quote(&ring[(index + 1) % ring.len()].name)));
+    }
+
+    // Every output shows the workspace it got, the focused one last
+    let lands_focused = |index: &usize| ring[(index + 1) % ring.len()].focused;
+    let (last, rest): (Vec<usize>, Vec<usize>) = (0..ring.len()).partition(lands_focused);
+    commands.extend(rest.into_iter().chain(last).map(|index| show(&shown[index])));
+    if let Some(name) = focused.filter(|f| !ring.iter().any(|o| o.name == f.name)).and_then(|f| f.current_workspace.as_deref()) {
+        commands.push(show(&quote(name)));
+    }
+    Some(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{Value, json};
+
+    use super::{balance, shares};
+    use crate::config::{NumberRange, WorkspaceSpec};
+    use crate::output::Output;
+    use crate::skip::Skip;
+
+    fn output(name: &str, x: i64) -> Output {
+        Output { name: name.to_string(), x, y: 0, width: 1920, height: 1080, focused: false, current_workspace: None }
+    }
+
+    fn workspace(num: i64, output: &str, focused: bool) -> Value {
+        json!({ "name": num.to_string(), "num": num, "output": output, "focused": focused })
+    }
+
+    #[test]
+    fn shares_follow_the_weights() {
+        assert_eq!(shares(&[1, 1], 4), [2, 2]);
+        assert_eq!(shares(&[2, 1], 6), [4, 2]);
+        assert_eq!(shares(&[1, 1, 1], 4), [2, 1, 1]);
+        assert_eq!(shares(&[3, 1], 5), [4, 1]);
+        assert_eq!(shares(&[0, 0], 3), [0, 0]);
+    }
+
+    #[test]
+    fn balance_moves_the_highest_numbers_to_the_emptiest_output() {
+        let outputs: Vec<Output> = vec![output("DP-1", 0), output("HDMI-A-1", 1920)];
+        let workspaces: Vec<Value> = (1..=4).map(|n| workspace(n, "DP-1", n == 1)).collect();
+        assert_eq!(balance(&HashMap::new(), &outputs, &HashMap::new(), &Skip::default(), &workspaces), [
+            "workspace \"3\"; move workspace to output \"HDMI-A-1\"",
+            "workspace \"4\"; move workspace to output \"HDMI-A-1\"",
+            "workspace \"1\"",
+        ]);
+    }
+
+    #[test]
+    fn balance_keeps_pinned_and_ranged_workspaces() {
+        let outputs: Vec<Output> = vec![output("DP-1", 0), output("HDMI-A-1", 1920)];
+        let workspaces: Vec<Value> = vec![
+            workspace(1, "DP-1", true), workspace(2, "DP-1", false), workspace(3, "DP-1", false), workspace(11, "DP-1", false),
+        ];
+        let ranges: HashMap<String, NumberRange> = HashMap::from([("HDMI-A-1".to_string(), NumberRange { first: 11, last: 20 })]);
+        let pinned: Skip = Skip::new(&[WorkspaceSpec::Number(3)]).unwrap();
+        assert_eq!(balance(&HashMap::new(), &outputs, &ranges, &pinned, &workspaces), [
+            "workspace \"11\"; move workspace to output \"HDMI-A-1\"",
+            "workspace \"2\"; move workspace to output \"HDMI-A-1\"",
+            "workspace \"1\"",
+        ]);
+    }
+
+    #[test]
+    fn balance_leaves_an_even_split_alone() {
+        let outputs: Vec<Output> = vec![output("DP-1", 0), output("HDMI-A-1", 1920)];
+        let workspaces: Vec<Value> = vec![workspace(1, "DP-1", true), workspace(2, "HDMI-A-1", false)];
+        assert!(balance(&HashMap::new(), &outputs, &HashMap::new(), &Skip::default(), &workspaces).is_empty());
+    }
+}
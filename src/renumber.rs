@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::config::NumberRange;
+use crate::output::{self, Output};
+use crate::skip::Skip;
+use crate::{num_of, order_key, quote};
+
+
+/// Prefix of the names workspaces have for a moment while they get their new numbers
+const TEMPORARY: &str = "sway-workspace-renumbering-";
+
+/// Old and new name of a workspace
+pub type Rename = (String, String);
+
+/// `name` with its number replaced by `num`, keeping what follows it, `3:web` becomes `5:web`
+pub fn with_number(name: &str, num: i64) -> String {
+    format!("{num}{}", name.trim_start_matches(|c: char| c.is_ascii_digit()))
+}
+
+/// Commands for renames from an old to a new name that run as one: a workspace renamed to a name
+/// another one still has gets a temporary name first
+pub fn commands(renames: &[Rename]) -> Vec<String> {
+    let renames: Vec<&Rename> = renames.iter().filter(|(from, to)| from != to).collect();
+    let (through, direct): (Vec<&Rename>, Vec<&Rename>) = renames.iter()
+        .partition(|(_, to)| renames.iter().any(|(from, _)| from == to));
+
+    let rename = |from: &str, to: &str| format!("rename workspace {} to {}", quote(from), quote(to));
+    let temporary = |index: usize| format!("{TEMPORARY}{index}");
+    through.iter().enumerate().map(|(index, (from, _))| rename(from, &temporary(index)))
+        .chain(direct.iter().map(|(from, to)| rename(from, to)))
+        .chain(through.iter().enumerate().map(|(index, (_, to))| rename(&temporary(index), to)))
+        .collect()
+}
+
+/// Number `workspaces` in the order given from `first` on, passing over the numbers in `taken`, which
+/// gets the ones handed out
+fn number_from(workspaces: &[&Value], first: i64, taken: &mut Vec<i64>) -> Vec<Rename> {
+    let mut num: i64 = first;
+    workspaces.iter()
+        .filter_map(|w| {
+            while taken.contains(&num) {
+                num += 1;
+            }
+            taken.push(num);
+            let name: &str = w["name"].as_str()?;
+            Some((name.to_string(), with_number(name, num)))
+        })
+        .collect()
+}
+
+/// Renames that number the workspaces 1 to n in their order, leaving pinned workspaces and the ones
+/// with only a name alone. Per output every output gets a block of its own, from the first number
+/// of its range if it has one
+pub fn compact(
+    workspaces: &[Value], outputs: &[Output], ranges: &HashMap<String, NumberRange>, pinned: &Skip, per_output: bool,
+) -> Vec<Rename> {
+    let mut taken: Vec<i64> = workspaces.iter().filter(|w| pinned.workspace(w)).filter_map(num_of).collect();
+    let mut movable: Vec<&Value> = workspaces.iter().filter(|w| num_of(w).is_some() && !pinned.workspace(w)).collect();
+    movable.sort_by_key(|w| order_key(w));
+
+    if !per_output {
+        return number_from(&movable, 1, &mut taken);
+    }
+    let mut renames: Vec<Rename> = Vec::new();
+    let mut next: i64 = 1;
+    for output in outputs {
+        let on_output: Vec<&Value> = movable.iter().copied().filter(|w| w["output"] == output.name.as_str()).collect();
+        let first: i64 = output::lookup(ranges, outputs, &output.name).map_or(next, |r| r.first);
+        let block: Vec<Rename> = number_from(&on_output, first, &mut taken);
+        next = next.max(taken.last().filter(|_| !block.is_empty()).map_or(next, |n| n + 1));
+        renames.extend(block);
+    }
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{Value, json};
+
+    use super::{Rename, commands, compact};
+    use crate::config::{NumberRange, WorkspaceSpec};
+    use crate::output::Output;
+    use crate::skip::Skip;
+
+    fn rename(from: &str, to: &str) -> Rename {
+        (from.to_string(), to.to_string())
+    }
+
+    fn output(name: &str, x: i64) -> Output {
+        Output { name: name.to_string(), x, y: 0, width: 1920, height: 1080, focused: false, current_workspace: None }
+    }
+
+    fn workspace(name: &str, num: i64, output: &str) -> Value {
+        json!({ "name": name, "num": num, "output": output })
+    }
+
+    #[test]
+    fn commands_rename_directly_without_collisions() {
+        assert_eq!(commands(&[rename("3", "2"), rename("1", "1")]), ["rename workspace \"3\" to \"2\""]);
+    }
+
+    #[test]
+    fn commands_swap_through_temporary_names() {
+        assert_eq!(commands(&[rename("1", "2"), rename("2", "1")]), [
+            "rename workspace \"1\" to \"sway-workspace-renumbering-0\"",
+            "rename workspace \"2\" to \"sway-workspace-renumbering-1\"",
+            "rename workspace \"sway-workspace-renumbering-0\" to \"2\"",
+            "rename workspace \"sway-workspace-renumbering-1\" to \"1\"",
+        ]);
+    }
+
+    #[test]
+    fn commands_chain_frees_a_name_before_it_is_taken() {
+        assert_eq!(commands(&[rename("2", "1"), rename("3", "2")]), [
+            "rename workspace \"3\" to \"sway-workspace-renumbering-0\"",
+            "rename workspace \"2\" to \"1\"",
+            "rename workspace \"sway-workspace-renumbering-0\" to \"2\"",
+        ]);
+    }
+
+    #[test]
+    fn compact_closes_gaps_around_pinned_workspaces() {
+        let workspaces: Vec<Value> = vec![
+            workspace("1", 1, "DP-1"), workspace("2", 2, "DP-1"), workspace("4:web", 4, "DP-1"), workspace("7", 7, "DP-1"),
+            workspace("chat", -1, "DP-1"),
+        ];
+        let pinned: Skip = Skip::new(&[WorkspaceSpec::Number(2)]).unwrap();
+        let renames: Vec<Rename> = compact(&workspaces, &[output("DP-1", 0)], &HashMap::new(), &pinned, false);
+        assert_eq!(renames, [rename("1", "1"), rename("4:web", "3:web"), rename("7", "4")]);
+    }
+
+    #[test]
+    fn compact_per_output_gives_each_output_a_block() {
+        let workspaces: Vec<Value> = vec![
+            workspace("1", 1, "DP-1"), workspace("2", 2, "HDMI-A-1"), workspace("4", 4, "DP-1"), workspace("15", 15, "eDP-1"),
+        ];
+        let outputs: Vec<Output> = vec![output("DP-1", 0), output("HDMI-A-1", 1920), output("eDP-1", 3840)];
+        let ranges: HashMap<String, NumberRange> = HashMap::from([("eDP-1".to_string(), NumberRange { first: 11, last: 20 })]);
+        let renames: Vec<Rename> = compact(&workspaces, &outputs, &ranges, &Skip::default(), true);
+        assert_eq!(renames, [rename("1", "1"), rename("4", "2"), rename("2", "3"), rename("15", "11")]);
+    }
+}
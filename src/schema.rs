@@ -0,0 +1,124 @@
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::display;
+use crate::state::State;
+use crate::tree::Node;
+
+
+/// Version of the JSON output, bumped only for changes that can break readers; new fields may come within a version
+pub const VERSION: u64 = 1;
+
+/// JSON Schema of what `--format json` prints, printed by `schema`
+pub const SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/krlohnes/sway-workspace/schema/v1.json",
+  "title": "sway-workspace output",
+  "type": "object",
+  "required": ["schema_version"],
+  "properties": {
+    "schema_version": { "const": 1 },
+    "workspace": {
+      "oneOf": [{ "$ref": "#/$defs/workspace" }, { "type": "null" }],
+      "description": "Null when there is none, like no previous workspace for query"
+    },
+    "output": { "type": ["string", "null"], "description": "Output a query or a followed event was about" },
+    "change": { "type": "string", "description": "Kind of workspace event printed by follow, like focus, init or rename" },
+    "old": {
+      "oneOf": [{ "$ref": "#/$defs/workspace" }, { "type": "null" }],
+      "description": "Workspace focused before a focus event printed by follow"
+    },
+    "windows": { "type": "array", "items": { "$ref": "#/$defs/window" }, "description": "Windows listed by windows" },
+    "workspaces": {
+      "type": "array",
+      "items": { "$ref": "#/$defs/workspace" },
+      "description": "Every workspace, listed by GET /workspaces of the daemon's HTTP endpoint and by deck"
+    },
+    "buttons": {
+      "type": "object",
+      "additionalProperties": { "type": "string" },
+      "description": "Action line per button identifier, printed by deck"
+    },
+    "project_dir": { "type": ["string", "null"] }
+  },
+  "$defs": {
+    "workspace": {
+      "type": "object",
+      "required": ["num", "name", "output", "color", "tags"],
+      "properties": {
+        "num": { "type": ["integer", "null"], "description": "Workspace number, null for workspaces without one" },
+        "name": { "type": "string" },
+        "display": { "type": "string", "description": "Name with the number shown in the configured numbering" },
+        "output": { "type": ["string", "null"], "description": "Output name, null when not known yet" },
+        "focused": { "type": "boolean" },
+        "visible": { "type": "boolean" },
+        "color": { "type": ["string", "null"], "description": "Color from label set" },
+        "tags": { "type": "array", "items": { "type": "string" }, "description": "Tags from label set" }
+      }
+    },
+    "window": {
+      "type": "object",
+      "required": ["id", "app_id", "title", "rect", "floating", "fullscreen"],
+      "properties": {
+        "id": { "type": "integer", "description": "Container id, for [con_id=...] criteria" },
+        "app_id": { "type": ["string", "null"], "description": "Wayland app id, or the class of X11 windows" },
+        "title": { "type": ["string", "null"] },
+        "rect": {
+          "type": "object",
+          "properties": {
+            "x": { "type": "integer" },
+            "y": { "type": "integer" },
+            "width": { "type": "integer" },
+            "height": { "type": "integer" }
+          }
+        },
+        "floating": { "type": "boolean" },
+        "fullscreen": { "type": "boolean" }
+      }
+    }
+  }
+}
+"##;
+
+/// A workspace as the schema describes it, fields missing from `workspace` come out as null
+pub fn workspace(config: &Config, state: &State, workspace: &Value) -> Value {
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+    let label = state.labels.get(name).cloned().unwrap_or_default();
+    json!({
+        "num": workspace["num"].as_i64().filter(|n| *n >= 0),
+        "name": name,
+        "display": display::name(config, name),
+        "output": workspace["output"].as_str(),
+        "focused": workspace["focused"] == true,
+        "visible": workspace["visible"] == true,
+        "color": label.color,
+        "tags": label.tags,
+    })
+}
+
+/// Wrap fields of an output document with the schema version
+pub fn document(fields: Value) -> Value {
+    let mut document = json!({ "schema_version": VERSION });
+    if let (Some(document), Value::Object(fields)) = (document.as_object_mut(), fields) {
+        document.extend(fields);
+    }
+    document
+}
+
+/// App id of a window, or the class for X11 windows
+pub fn app_id(window: &Node) -> Option<&str> {
+    window.app_id.as_deref().or_else(|| window.window_properties.as_ref()?.class.as_deref())
+}
+
+/// A window as the schema describes it
+pub fn window(window: &Node, floating: bool) -> Value {
+    let rect = &window.rect;
+    json!({
+        "id": window.id,
+        "app_id": app_id(window),
+        "title": window.name,
+        "rect": { "x": rect.x, "y": rect.y, "width": rect.width, "height": rect.height },
+        "floating": floating,
+        "fullscreen": window.fullscreen_mode > 0,
+    })
+}
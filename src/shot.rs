@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+
+use ksway::{Client, ipc_command};
+use serde_json::Value;
+
+use crate::{Error, ipc, quote};
+
+
+/// Time for the output to show the revealed workspace before it is captured
+const SETTLE: Duration = Duration::from_millis(200);
+
+fn grim(output: &str, file: &Path) -> Result<(), Error> {
+    let status = Command::new("grim").arg("-o").arg(output).arg(file).status()
+        .map_err(|e| Error::Failed(format!("grim: {e}")))?;
+    if !status.success() {
+        return Err(Error::Failed(format!("grim failed with {status}")));
+    }
+    Ok(())
+}
+
+/// Capture the output showing `workspace` into `file`, a hidden workspace is shown for the capture
+/// when `reveal` is set and the output's workspace and the focus are put back afterwards
+pub fn shot(client: &mut Client, workspaces: &[Value], workspace: &Value, file: &Path, reveal: bool) -> Result<(), Error> {
+    let name: &str = workspace["name"].as_str().unwrap_or_default();
+    let output: &str = workspace["output"].as_str().unwrap_or_default();
+    if workspace["visible"] == true {
+        return grim(output, file);
+    }
+    if !reveal {
+        return Err(Error::Failed(format!("workspace {name} is not visible, --reveal shows it for the shot")));
+    }
+
+    let shown: Option<&str> = workspaces.iter()
+        .find(|w| w["output"] == output && w["visible"] == true)
+        .and_then(|w| w["name"].as_str());
+    let focused: Option<&str> = workspaces.iter().find(|w| w["focused"] == true).and_then(|w| w["name"].as_str());
+
+    ipc(client, ipc_command::run(format!("workspace {}", quote(name))))?;
+    sleep(SETTLE);
+    let result = grim(output, file);
+
+    let mut restore: Vec<String> = [shown, focused].into_iter().flatten()
+        .map(|w| format!("workspace {}", quote(w)))
+        .collect();
+    restore.dedup();
+    if !restore.is_empty() {
+        ipc(client, ipc_command::run(restore.join("; ")))?;
+    }
+    result
+}
@@ -0,0 +1,82 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::WorkspaceSpec;
+use crate::num_of;
+
+
+/// Workspaces navigation passes over, from `--skip` and `[navigation] skip`
+#[derive(Default)]
+pub struct Skip {
+    numbers: Vec<i64>,
+    names: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Skip {
+    /// Numbers and names as they are, names between slashes like `/^tmp/` are regular expressions
+    pub fn new<'a>(specs: impl IntoIterator<Item = &'a WorkspaceSpec>) -> Result<Skip, String> {
+        let mut skip = Skip::default();
+        for spec in specs {
+            match spec {
+                WorkspaceSpec::Number(num) => skip.numbers.push(*num),
+                WorkspaceSpec::Name(name) => match name.strip_prefix('/').and_then(|n| n.strip_suffix('/')) {
+                    Some(pattern) => skip.patterns.push(Regex::new(pattern).map_err(|e| format!("skip {name}: {e}"))?),
+                    None => match name.parse::<i64>() {
+                        Ok(num) => skip.numbers.push(num),
+                        Err(_) => skip.names.push(name.to_owned()),
+                    },
+                },
+            }
+        }
+        Ok(skip)
+    }
+
+    /// Whether a new workspace by that number, which sway names after it, is skipped
+    pub fn number(&self, num: i64) -> bool {
+        let name: String = num.to_string();
+        self.numbers.contains(&num) || self.names.contains(&name) || self.patterns.iter().any(|p| p.is_match(&name))
+    }
+
+    pub fn workspace(&self, workspace: &Value) -> bool {
+        let name: &str = workspace["name"].as_str().unwrap_or_default();
+        num_of(workspace).is_some_and(|n| self.numbers.contains(&n))
+            || self.names.iter().any(|n| n == name)
+            || self.patterns.iter().any(|p| p.is_match(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Skip;
+    use crate::config::WorkspaceSpec;
+
+    fn skip(specs: &[&str]) -> Skip {
+        let specs: Vec<WorkspaceSpec> = specs.iter().map(|s| WorkspaceSpec::Name(s.to_string())).collect();
+        Skip::new(&specs).unwrap()
+    }
+
+    #[test]
+    fn new_reads_numbers_names_and_patterns() {
+        let skip: Skip = skip(&["3", "chat", "/^tmp/"]);
+        assert!(skip.workspace(&json!({ "name": "3:mail", "num": 3 })));
+        assert!(skip.workspace(&json!({ "name": "chat", "num": -1 })));
+        assert!(skip.workspace(&json!({ "name": "tmp-build", "num": -1 })));
+        assert!(!skip.workspace(&json!({ "name": "4:web", "num": 4 })));
+    }
+
+    #[test]
+    fn number_matches_the_name_sway_gives_a_new_workspace() {
+        let skip: Skip = skip(&["/^1[0-9]$/"]);
+        assert!(skip.number(12));
+        assert!(!skip.number(2));
+        assert!(Skip::new(&[WorkspaceSpec::Number(5)]).unwrap().number(5));
+    }
+
+    #[test]
+    fn new_fails_on_a_broken_pattern() {
+        assert!(Skip::new(&[WorkspaceSpec::Name("/(/".to_string())]).is_err());
+    }
+}
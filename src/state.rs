@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::env::var;
+use std::fs::{File, OpenOptions, create_dir_all, read_to_string, rename, write};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string_pretty};
+
+
+/// Entries kept in the history for `back` and `forward`
+const HISTORY_LEN: usize = 100;
+
+/// Data kept between invocations in `$XDG_STATE_HOME/sway-workspace/state.json`
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct State {
+    pub bookmarks: BTreeMap<String, WorkspaceRef>,
+
+    /// Workspaces remembered by `push`, most recent last
+    pub stack: Vec<WorkspaceRef>,
+
+    /// How often and how recently each workspace name was switched to
+    pub visits: BTreeMap<String, Visit>,
+
+    /// Color and tags per workspace name, set with `label set`
+    pub labels: BTreeMap<String, Label>,
+
+    /// Group per workspace name, set with `group move`, and the group `group switch` made active
+    pub groups: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Workspace each output showed before its current one
+    pub previous: BTreeMap<String, WorkspaceRef>,
+
+    /// Workspaces switched to, oldest first, `back` and `forward` move `history_pos` along them
+    pub history: Vec<WorkspaceRef>,
+    pub history_pos: usize,
+
+    /// Workspace focused before the current one on any output, for `back-and-forth`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focused_before: Option<WorkspaceRef>,
+
+    /// Workspace each output showed last and the output that had the focus, kept by the daemon
+    /// to put them back after sway reloads or restarts
+    pub shown: BTreeMap<String, WorkspaceRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focused_output: Option<String>,
+
+    /// Workspaces there were when the daemon last looked, with `[daemon] session`
+    pub session: Vec<WorkspaceRef>,
+
+    /// Profile activated by hand, it wins over the schedule until the schedule next changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Label {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Visit {
+    pub count: u64,
+    /// Unix time of the last visit in seconds
+    pub last: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+impl Visit {
+    /// Frequency weighted by recency, in the spirit of Firefox's frecency buckets
+    pub fn frecency(&self, now: u64) -> u64 {
+        let age: u64 = now.saturating_sub(self.last);
+        let weight: u64 = match age {
+            a if a < 4 * 3600 => 100,
+            a if a < 24 * 3600 => 80,
+            a if a < 7 * 24 * 3600 => 60,
+            a if a < 30 * 24 * 3600 => 40,
+            _ => 20,
+        };
+        self.count * weight
+    }
+}
+
+impl State {
+    /// Whether the workspace by that name is in another group than the active one, which hides it
+    pub fn in_other_group(&self, name: &str) -> bool {
+        self.group.as_ref().is_some_and(|active| self.groups.get(name).is_some_and(|g| g != active))
+    }
+
+    pub fn record_visit(&mut self, name: &str) {
+        let visit = self.visits.entry(name.to_string()).or_default();
+        visit.count += 1;
+        visit.last = now();
+    }
+
+    /// Remember `from` as the workspace focused before, and as the previous workspace of its output when
+    /// the switch stays on that output
+    pub fn record_switch(&mut self, from: &serde_json::Value, to_output: &str) {
+        self.focused_before = Some(WorkspaceRef::from_value(from));
+        if from["output"] == to_output {
+            self.previous.insert(to_output.to_string(), WorkspaceRef::from_value(from));
+        }
+    }
+
+    /// Add a switch from `from` to `to` to the history, unless it is `back` or `forward` arriving where
+    /// they moved the position to
+    pub fn record_history(&mut self, from: &serde_json::Value, to: &serde_json::Value) {
+        if self.history.get(self.history_pos).is_some_and(|h| to["name"] == h.name.as_str()) {
+            return;
+        }
+        self.push_history(from);
+        self.push_history(to);
+    }
+
+    /// The workspace `step` entries away in the history, moving the position there; a `current` that
+    /// is not where the position is joins the history first
+    pub fn step_history(&mut self, current: &serde_json::Value, step: i64) -> Option<WorkspaceRef> {
+        if self.history.get(self.history_pos).is_none_or(|h| current["name"] != h.name.as_str()) {
+            self.push_history(current);
+        }
+        let pos: usize = usize::try_from(self.history_pos as i64 + step).ok().filter(|p| *p < self.history.len())?;
+        self.history_pos = pos;
+        Some(self.history[pos].to_owned())
+    }
+
+    /// Drop the entries ahead of the position and append `workspace`, it becomes the position
+    fn push_history(&mut self, workspace: &serde_json::Value) {
+        self.history.truncate(self.history_pos + 1);
+        if self.history.last().is_none_or(|h| workspace["name"] != h.name.as_str()) {
+            self.history.push(WorkspaceRef::from_value(workspace));
+        }
+        if self.history.len() > HISTORY_LEN {
+            self.history.drain(..self.history.len() - HISTORY_LEN);
+        }
+        self.history_pos = self.history.len() - 1;
+    }
+
+    /// Remember the focused workspace as the one its output shows
+    pub fn record_shown(&mut self, focused: &serde_json::Value) {
+        let output: String = focused["output"].as_str().unwrap_or_default().to_string();
+        self.shown.insert(output.to_owned(), WorkspaceRef::from_value(focused));
+        self.focused_output = Some(output);
+    }
+
+    pub fn frecency(&self, name: &str) -> u64 {
+        self.visits.get(name).map(|v| v.frecency(now())).unwrap_or_default()
+    }
+}
+
+/// A workspace remembered by its container id, name and output, so it can be found again after renames
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkspaceRef {
+    pub id: i64,
+    pub num: i64,
+    pub name: String,
+    pub output: String,
+}
+
+impl WorkspaceRef {
+    pub fn from_value(workspace: &serde_json::Value) -> WorkspaceRef {
+        WorkspaceRef {
+            id: workspace["id"].as_i64().unwrap_or(-1),
+            num: workspace["num"].as_i64().unwrap_or(-1),
+            name: workspace["name"].as_str().unwrap_or_default().to_string(),
+            output: workspace["output"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Find the workspace again: same container, then same name, then same label on the same output
+    pub fn resolve<'a>(&self, workspaces: &'a [serde_json::Value]) -> Option<&'a serde_json::Value> {
+        let label = |name: &str| name.split_once(':').map(|(_, l)| l.to_string());
+        workspaces.iter().find(|w| w["id"] == self.id)
+            .or_else(|| workspaces.iter().find(|w| w["name"] == self.name.as_str()))
+            .or_else(|| {
+                let wanted = label(&self.name)?;
+                workspaces.iter().find(|w| {
+                    w["output"] == self.output.as_str() && w["name"].as_str().and_then(label) == Some(wanted.to_owned())
+                })
+            })
+    }
+}
+
+/// Directory for sockets and other files that only live as long as the session
+pub fn runtime_dir() -> PathBuf {
+    match var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from("/tmp"),
+    }
+}
+
+pub fn state_path() -> PathBuf {
+    let base = match var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(var("HOME").unwrap_or_default()).join(".local").join("state"),
+    };
+    base.join("sway-workspace").join("state.json")
+}
+
+/// File `layout save <NAME>` writes, next to the state file
+pub fn layout_path(name: &str) -> PathBuf {
+    state_path().with_file_name("layouts").join(format!("{name}.json"))
+}
+
+/// Load the state file, a missing or unreadable file yields an empty state
+pub fn load() -> State {
+    read_to_string(state_path()).ok()
+        .and_then(|content| from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Load the state, let `change` change it and save it, holding a lock on the file next to it all the
+/// while so that the daemon and an action running at the same time do not lose what the other saved.
+/// The new state goes to a file of this process that replaces the state file in one rename
+pub fn update<T>(change: impl FnOnce(&mut State) -> T) -> Result<T, String> {
+    let path = state_path();
+    let failed = |e: std::io::Error| format!("{}: {e}", path.display());
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir).map_err(failed)?;
+    }
+    // The lock goes away with the file handle
+    let lock: File = OpenOptions::new().create(true).truncate(false).write(true).open(path.with_extension("lock")).map_err(failed)?;
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(failed(std::io::Error::last_os_error()));
+    }
+
+    let mut state: State = load();
+    let changed: T = change(&mut state);
+    let tmp = path.with_extension(format!("json.{}", process::id()));
+    write(&tmp, to_string_pretty(&state).map_err(std::io::Error::from).map_err(failed)?)
+        .and_then(|_| rename(&tmp, &path))
+        .map_err(failed)?;
+    Ok(changed)
+}
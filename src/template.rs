@@ -0,0 +1,37 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ksway::{Client, ipc_command};
+
+use crate::{Error, ipc, tree};
+use crate::config::Template;
+
+
+/// How often the tree is looked at while waiting for the windows of a template
+const POLL: Duration = Duration::from_millis(100);
+
+/// Start the apps of a template on the focused workspace `num`, then run its commands there, once
+/// its windows are up when it waits for them
+pub fn launch(client: &mut Client, template: &Template, num: i64) -> Result<(), Error> {
+    for app in &template.apps {
+        ipc(client, ipc_command::run(format!("exec {app}")))?;
+    }
+    if template.commands.is_empty() {
+        return Ok(());
+    }
+
+    if template.wait {
+        let deadline = Instant::now() + Duration::from_millis(template.timeout);
+        while Instant::now() < deadline {
+            let tree: tree::Node = tree::get_tree(client)?;
+            let windows: usize = tree::find_workspace_number(&tree, num).map_or(0, |w| tree::windows(w).len());
+            if windows >= template.apps.len() {
+                break;
+            }
+            thread::sleep(POLL);
+        }
+    }
+    let commands = std::iter::once(format!("workspace number {num}")).chain(template.commands.iter().cloned());
+    ipc(client, ipc_command::run(commands.collect::<Vec<String>>().join("; ")))?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use ksway::{Client, ipc_command};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::EmptyConfig;
+use crate::{Error, ipc, reply};
+
+
+/// The parts of a tree node the actions look at, everything else in the reply is skipped while parsing
+/// instead of being built into a `Value`, which keeps big sessions cheap
+#[derive(Deserialize)]
+pub struct Node {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Number of a workspace, -1 for one with only a name and missing on other nodes
+    #[serde(default)]
+    pub num: Option<i64>,
+    #[serde(default)]
+    pub focused: bool,
+    /// 0 when not fullscreen, 1 fullscreen on its workspace, 2 global fullscreen
+    #[serde(default)]
+    pub fullscreen_mode: i64,
+    /// How the children are arranged, `splith`, `splitv`, `tabbed` or `stacked`
+    #[serde(default)]
+    pub layout: String,
+    /// Share of the parent, missing for workspaces and floating windows
+    #[serde(default)]
+    pub percent: Option<f64>,
+    #[serde(default)]
+    pub rect: Rect,
+    /// Wayland app id, X11 windows have a class in `window_properties` instead
+    #[serde(default)]
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    pub marks: Vec<String>,
+    #[serde(default)]
+    pub sticky: bool,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub floating_nodes: Vec<Node>,
+    /// Ids of the children, most recently focused first
+    #[serde(default)]
+    pub focus: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct WindowProperties {
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+/// Which windows an action is after, every field given has to match
+#[derive(Clone, Default)]
+pub struct Criteria {
+    pub app_id: Option<String>,
+    /// X11 class
+    pub class: Option<String>,
+    pub title: Option<Regex>,
+}
+
+impl Criteria {
+    pub fn matches(&self, window: &Node) -> bool {
+        let class: Option<&str> = window.window_properties.as_ref().and_then(|p| p.class.as_deref());
+        self.app_id.as_ref().is_none_or(|a| window.app_id.as_ref() == Some(a))
+            && self.class.as_deref().is_none_or(|c| class == Some(c))
+            && self.title.as_ref().is_none_or(|t| window.name.as_deref().is_some_and(|n| t.is_match(n)))
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct Rect {
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+pub fn get_tree(client: &mut Client) -> Result<Node, Error> {
+    reply(&ipc(client, ipc_command::get_tree())?)
+}
+
+/// Tiling and floating children of a container
+pub fn children(node: &Node) -> impl Iterator<Item = &Node> {
+    node.nodes.iter().chain(node.floating_nodes.iter())
+}
+
+/// Find the workspace node with the given name
+pub fn find_workspace<'a>(node: &'a Node, name: &str) -> Option<&'a Node> {
+    if node.kind == "workspace" && node.name.as_deref() == Some(name) {
+        return Some(node);
+    }
+    children(node).find_map(|child| find_workspace(child, name))
+}
+
+/// Find the workspace with number `num`, whatever its name says after the number
+pub fn find_workspace_number(node: &Node, num: i64) -> Option<&Node> {
+    if node.kind == "workspace" && node.num == Some(num) {
+        return Some(node);
+    }
+    children(node).find_map(|child| find_workspace_number(child, num))
+}
+
+/// Find the focused container
+pub fn find_focused(node: &Node) -> Option<&Node> {
+    if node.focused {
+        return Some(node);
+    }
+    children(node).find_map(find_focused)
+}
+
+/// The window that was focused last below a container, following the focus order down to a leaf
+pub fn last_focused(node: &Node) -> Option<&Node> {
+    let Some(id) = node.focus.first() else {
+        return (node.kind == "con" || node.kind == "floating_con").then_some(node);
+    };
+    children(node).find(|c| c.id == *id).and_then(last_focused)
+}
+
+/// Whether a container below `node` is fullscreen
+pub fn has_fullscreen(node: &Node) -> bool {
+    node.fullscreen_mode > 0 || children(node).any(has_fullscreen)
+}
+
+/// Number of windows on a workspace that keep it from being empty
+pub fn count_occupying(node: &Node, empty: &EmptyConfig) -> usize {
+    let ignored = |window: &Node| {
+        let class: Option<&str> = window.window_properties.as_ref().and_then(|p| p.class.as_deref());
+        [window.app_id.as_deref(), class, window.name.as_deref()].into_iter()
+            .flatten()
+            .any(|s| empty.ignore_floating.iter().any(|i| i == s))
+    };
+    windows(node).into_iter()
+        .filter(|(window, floating)| !((empty.ignore_sticky && window.sticky) || (*floating && ignored(window))))
+        .count()
+}
+
+/// Whether the container `id` is `node` or below it
+pub fn contains(node: &Node, id: i64) -> bool {
+    node.id == id || children(node).any(|c| contains(c, id))
+}
+
+/// Find the container that has `id` as a child
+pub fn parent_of(node: &Node, id: i64) -> Option<&Node> {
+    if children(node).any(|c| c.id == id) {
+        return Some(node);
+    }
+    children(node).find_map(|child| parent_of(child, id))
+}
+
+/// Find the workspace a container is on
+pub fn workspace_of(node: &Node, id: i64) -> Option<&Node> {
+    if node.kind == "workspace" {
+        return contains(node, id).then_some(node);
+    }
+    children(node).find_map(|child| workspace_of(child, id))
+}
+
+/// Windows below `node` in tree order, each with whether it floats
+pub fn windows(node: &Node) -> Vec<(&Node, bool)> {
+    fn walk<'a>(node: &'a Node, floating: bool, windows: &mut Vec<(&'a Node, bool)>) {
+        if node.kind != "workspace" && node.nodes.is_empty() && node.floating_nodes.is_empty() {
+            windows.push((node, floating));
+        }
+        node.nodes.iter().for_each(|c| walk(c, floating, windows));
+        node.floating_nodes.iter().for_each(|c| walk(c, true, windows));
+    }
+    let mut windows = Vec::new();
+    walk(node, false, &mut windows);
+    windows
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+use serde_json::Value;
+
+use crate::config::{Config, WallpaperTool};
+use crate::num_of;
+
+
+/// The wallpaper an output shows, with the swaybg drawing it
+struct Shown {
+    file: String,
+    swaybg: Option<Child>,
+}
+
+/// Wallpapers the daemon set per output, so a switch between workspaces with the same one does nothing
+#[derive(Default)]
+pub struct Wallpapers {
+    shown: HashMap<String, Shown>,
+}
+
+impl Wallpapers {
+    /// Put up the wallpaper of `workspace` on the output it is on
+    pub fn show(&mut self, config: &Config, workspace: &Value) {
+        let (Some(name), Some(output)) = (workspace["name"].as_str(), workspace["output"].as_str()) else {
+            return;
+        };
+        let Some(file) = config.wallpaper.file(name, num_of(workspace)) else {
+            return;
+        };
+        if self.shown.get(output).is_some_and(|s| s.file == file) {
+            return;
+        }
+
+        let mut command: Command = match config.wallpaper.tool {
+            WallpaperTool::Swaybg => {
+                let mut command = Command::new("swaybg");
+                command.args(["-o", output, "-i", file, "-m", &config.wallpaper.mode]);
+                command
+            },
+            WallpaperTool::Swww => {
+                let mut command = Command::new("swww");
+                command.args(["img", "-o", output, file]);
+                command
+            },
+        };
+        let child: Child = match command.stdin(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("wallpaper {file}: {err}");
+                return;
+            },
+        };
+
+        // swaybg keeps running to draw and replaces the one started before, swww returns right away
+        let swaybg: Option<Child> = match config.wallpaper.tool {
+            WallpaperTool::Swaybg => Some(child),
+            WallpaperTool::Swww => {
+                let mut child = child;
+                thread::spawn(move || child.wait());
+                None
+            },
+        };
+        let old: Option<Shown> = self.shown.insert(output.to_string(), Shown { file: file.to_string(), swaybg });
+        if let Some(mut old) = old.and_then(|o| o.swaybg) {
+            let _ = old.kill();
+            thread::spawn(move || old.wait());
+        }
+    }
+}